@@ -7,15 +7,18 @@ use crate::rvkp::mesh::Mesh;
 use crate::rvkp::vk_impl;
 use crate::rvkp::vk_renderer;
 use crate::rvkp::vk_renderer::Renderer;
-use crate::rvkp::{init::Vk, presenter::{VkPresenter, VkView, WINDOW_RESIZED}};
 use crate::application::App;
-use crate::rvkp::presenter::vert;
+use crate::mesh_gen::{self, MESH_POOL};
 
 use std::sync::{Arc, Mutex};
 use once_cell::sync::Lazy;
 
 use crate::utils::random;
-use crate::mesh_gen::{VOXGEN_CH, VoxelMeshGenJob};
+use crate::voxel::STONE;
+use crate::world::WORLD;
+
+// how far away blocks can be dug or placed
+const REACH: f32 = 8.0;
 
 use tokio::spawn;
 
@@ -33,7 +36,64 @@ pub async fn run(event_loop: EventLoop<()>, renderer: Arc<Mutex<Renderer>>) {
                 event: WindowEvent::Resized(_),
                 ..
             } => {
-                //renderer.lock().unwrap().presenter.recreate_swapchain = true;
+                // picked up by the next frame, which also waits out a minimized window
+                renderer.lock().unwrap().presenter.recreate_swapchain = true;
+            },
+
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::F9),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                    ..
+                },
+                ..
+            } => {
+                let mut renderer = renderer.lock().unwrap();
+                let vk_clone = renderer.vk_impl.clone();
+                let mut vk = vk_clone.lock().unwrap();
+
+                let vsync = vk.vsync.next();
+                renderer.presenter.set_vsync(&mut vk, vsync);
+                println!("vsync {vsync:?}");
+            },
+
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput {
+                        virtual_keycode: Some(VirtualKeyCode::F12),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                    ..
+                },
+                ..
+            } => {
+                let secs = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                let path = format!("screenshot-{secs}.png");
+
+                match renderer.lock().unwrap().capture_frame(&path) {
+                    Ok(()) => println!("saved {path}"),
+                    Err(e) => println!("failed to capture frame: {e}"),
+                }
+            },
+
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state: ElementState::Pressed, button, .. },
+                ..
+            } => {
+                let renderer = renderer.lock().unwrap();
+                let (pos, front) = (renderer.camera.pos, renderer.camera.front);
+
+                match button {
+                    MouseButton::Left => { WORLD.lock().unwrap().dig(pos, front, REACH); },
+                    MouseButton::Right => { WORLD.lock().unwrap().place(pos, front, REACH, STONE); },
+                    _ => (),
+                }
             },
 
             Event::WindowEvent {
@@ -47,16 +107,38 @@ pub async fn run(event_loop: EventLoop<()>, renderer: Arc<Mutex<Renderer>>) {
 
             },
 
+            Event::LoopDestroyed => {
+                // chunks still loaded, evicted ones were saved as they went
+                match WORLD.lock().unwrap().save() {
+                    Ok(()) => println!("world saved"),
+                    Err(e) => println!("failed to save the world: {e}"),
+                }
+            }
+
             Event::MainEventsCleared => {
                 let renderer_clone = renderer.clone();
                 let mut renderer = renderer_clone.lock().unwrap();
                 let vk_clone = renderer.vk_impl.clone();
 
-                std::sync::Once::new().call_once(|| {
-                    renderer.meshes.push(Mesh::quad(&vk_clone.lock().unwrap()));
-                });
+                if renderer.scene.find("quad").is_none() {
+                    match Mesh::quad(&vk_clone.lock().unwrap()) {
+                        Ok(quad) => {
+                            let root = renderer.scene.root();
+                            renderer.scene.add_mesh(root, "quad", quad);
+                        }
+                        Err(e) => println!("failed to create the quad: {e}"),
+                    }
+                }
+
+                let camera_pos = renderer.camera.pos;
+                mesh_gen::stream(camera_pos, &mut renderer);
+                mesh_gen::remesh_dirty();
+                MESH_POOL.upload(&mut renderer);
 
-                renderer.update();
+                if let Err(e) = renderer.update() {
+                    println!("{e}");
+                    *control_flow = ControlFlow::Exit;
+                }
             }
 
             _ => () 
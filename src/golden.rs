@@ -0,0 +1,255 @@
+// golden-image regression tests. every scene is rendered headless at a fixed resolution and
+// compared against the reference PNGs in golden/. they need a vulkan device so they're ignored
+// by default, run with
+//
+//   cargo test golden -- --ignored                        compare against the references
+//   RVKP_GOLDEN_BLESS=1 cargo test golden -- --ignored    (re)write them from the current renderer
+//
+// on a mismatch the rendered frame and a diff image (mismatching pixels in red over a dimmed
+// reference) are written to target/golden/
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use glam::{vec3a, Vec3A};
+
+use crate::mesh_gen::{VoxelMeshGen, VoxelMeshGenJob, CHUNK_SIZE};
+use crate::rvkp::capture;
+use crate::rvkp::debug::DebugConfig;
+use crate::rvkp::device::DevicePolicy;
+use crate::rvkp::error::RvkpError;
+use crate::rvkp::mesh::Mesh;
+use crate::rvkp::vk_impl::VkImpl;
+use crate::rvkp::vk_renderer::Renderer;
+use crate::terrain::{Heightmap, Terrain};
+
+const GOLDEN_DIR: &str = "golden";
+const OUT_DIR: &str = "target/golden";
+
+// square, so the references stay valid if the projection setup changes
+const EXTENT: [u32; 2] = [256, 256];
+// max difference per channel before a pixel counts as different, absorbs driver rounding
+const TOLERANCE: u8 = 2;
+const SEED: u64 = 42;
+const BLESS_VAR: &str = "RVKP_GOLDEN_BLESS";
+
+struct Frame {
+    name: String,
+    pixels: Vec<u8>,
+}
+
+type Scene = fn(&mut Renderer) -> Result<Vec<Frame>, RvkpError>;
+
+#[tokio::test]
+#[ignore = "needs a vulkan device"]
+async fn golden_quad() {
+    run_scene(scene_quad).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a vulkan device"]
+async fn golden_chunk() {
+    run_scene(scene_chunk).await;
+}
+
+#[tokio::test]
+#[ignore = "needs a vulkan device"]
+async fn golden_fly() {
+    run_scene(scene_fly).await;
+}
+
+// renders the scene on a fresh headless renderer and checks (or blesses) every frame of it
+async fn run_scene(render: Scene) {
+    let bless = std::env::var(BLESS_VAR).is_ok_and(|v| v == "1");
+
+    let renderer = start()
+        .await
+        .unwrap_or_else(|e| panic!("couldn't start the headless renderer: {e}"));
+    let mut renderer = renderer.lock().unwrap();
+    let frames = render(&mut renderer).unwrap_or_else(|e| panic!("couldn't render the scene: {e}"));
+
+    let mut failures = vec![];
+    for frame in frames {
+        let result = if bless {
+            bless_frame(&frame)
+        } else {
+            check_frame(&frame)
+        };
+
+        match result {
+            Ok(None) => println!("golden {}: ok", frame.name),
+            Ok(Some(msg)) => failures.push(format!("{}: {msg}", frame.name)),
+            Err(e) => failures.push(format!("{}: {e}", frame.name)),
+        }
+    }
+
+    assert!(failures.is_empty(), "golden frames failed:\n{}", failures.join("\n"));
+}
+
+async fn start() -> Result<Arc<Mutex<Renderer>>, RvkpError> {
+    // RVKP_GPU=cpu pins a software rasterizer, so references can be shared between machines
+    // RVKP_VALIDATION=panic fails the run on the first frame that triggered a validation error
+    let vk = VkImpl::headless(
+        EXTENT,
+        DevicePolicy::from_env_or(DevicePolicy::default()),
+        DebugConfig::from_env(),
+    )
+    .await?;
+    println!("golden: rendering on {}", vk.lock().unwrap().gpu.name);
+    let renderer = Renderer::new(vk.clone()).await?;
+    vk.lock().unwrap().ignition(renderer.clone())?;
+
+    Ok(renderer)
+}
+
+// SCENES //
+
+fn render(renderer: &mut Renderer, name: &str) -> Result<Frame, RvkpError> {
+    Ok(Frame {
+        name: name.to_string(),
+        pixels: renderer.render_offscreen()?,
+    })
+}
+
+fn scene_quad(renderer: &mut Renderer) -> Result<Vec<Frame>, RvkpError> {
+    let quad = Mesh::quad(&renderer.vk_impl.lock().unwrap())?;
+    let root = renderer.scene.root();
+    renderer.scene.add_mesh(root, "quad", quad);
+
+    Ok(vec![render(renderer, "quad")?])
+}
+
+// one heightmap chunk, same seed every run
+fn chunk_mesh(renderer: &Renderer) -> Result<Mesh, RvkpError> {
+    let voxels = Terrain::new(SEED, Heightmap::default()).chunk([0, 0, 0], CHUNK_SIZE);
+    let (verts, inds) = VoxelMeshGen::build(&VoxelMeshGenJob::from_voxels(voxels, Vec3A::ZERO));
+
+    Mesh::new(verts, inds, vec![], &renderer.vk_impl.lock().unwrap())
+}
+
+fn look_at(renderer: &mut Renderer, pos: Vec3A, target: Vec3A) {
+    renderer.camera.pos = pos;
+    renderer.camera.front = (target - pos).normalize();
+    renderer.camera.update();
+}
+
+fn scene_chunk(renderer: &mut Renderer) -> Result<Vec<Frame>, RvkpError> {
+    let mesh = chunk_mesh(renderer)?;
+    let root = renderer.scene.root();
+    renderer.scene.add_mesh(root, "chunk", mesh);
+
+    let center = Vec3A::splat(CHUNK_SIZE as f32 * 0.5);
+    look_at(renderer, vec3a(-20.0, 48.0, -20.0), center);
+
+    Ok(vec![render(renderer, "chunk")?])
+}
+
+// circles the chunk in fixed steps
+fn scene_fly(renderer: &mut Renderer) -> Result<Vec<Frame>, RvkpError> {
+    let mesh = chunk_mesh(renderer)?;
+    let root = renderer.scene.root();
+    renderer.scene.add_mesh(root, "chunk", mesh);
+
+    let center = Vec3A::splat(CHUNK_SIZE as f32 * 0.5);
+    let steps = 4;
+
+    (0..steps)
+        .map(|i| {
+            let angle = i as f32 / steps as f32 * std::f32::consts::TAU;
+            let pos = center + vec3a(angle.cos() * 48.0, 24.0, angle.sin() * 48.0);
+            look_at(renderer, pos, center);
+
+            render(renderer, &format!("fly_{i}"))
+        })
+        .collect()
+}
+
+// COMPARISON //
+
+fn golden_path(frame: &Frame) -> PathBuf {
+    Path::new(GOLDEN_DIR).join(format!("{}.png", frame.name))
+}
+
+// only the clear color, usually the camera looking away from the scene
+fn is_blank(frame: &Frame) -> bool {
+    let mut pixels = frame.pixels.chunks_exact(4);
+    let first = pixels.next();
+
+    pixels.all(|px| Some(px) == first)
+}
+
+fn bless_frame(frame: &Frame) -> io::Result<Option<String>> {
+    if is_blank(frame) {
+        return Ok(Some("frame is blank, refusing to bless it".to_string()));
+    }
+
+    fs::create_dir_all(GOLDEN_DIR)?;
+    write_rgba(&golden_path(frame), &frame.pixels)?;
+
+    Ok(None)
+}
+
+// Ok(Some(reason)) on a mismatch
+fn check_frame(frame: &Frame) -> io::Result<Option<String>> {
+    let path = golden_path(frame);
+    if !path.exists() {
+        return Ok(Some(format!("no reference at {}, bless with {BLESS_VAR}=1", path.display())));
+    }
+    if is_blank(frame) {
+        return Ok(Some("frame is blank".to_string()));
+    }
+
+    let expected = read_rgba(&path)?;
+    if expected.len() != frame.pixels.len() {
+        return Ok(Some(format!("reference is not {}x{}", EXTENT[0], EXTENT[1])));
+    }
+
+    let mut diff = Vec::with_capacity(expected.len());
+    let mut mismatched = 0;
+
+    for (a, e) in frame.pixels.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        let differs = (0..3).any(|c| a[c].abs_diff(e[c]) > TOLERANCE);
+
+        if differs {
+            mismatched += 1;
+            diff.extend([255, 0, 0, 255]);
+        } else {
+            let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 3) as u8;
+            diff.extend([gray, gray, gray, 255]);
+        }
+    }
+
+    if mismatched == 0 {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(OUT_DIR)?;
+    let out = Path::new(OUT_DIR);
+    write_rgba(&out.join(format!("{}.actual.png", frame.name)), &frame.pixels)?;
+    write_rgba(&out.join(format!("{}.diff.png", frame.name)), &diff)?;
+
+    Ok(Some(format!(
+        "{mismatched} pixels differ by more than {TOLERANCE}, see {}",
+        out.join(format!("{}.diff.png", frame.name)).display(),
+    )))
+}
+
+fn write_rgba(path: &Path, pixels: &[u8]) -> io::Result<()> {
+    capture::write_png(path, EXTENT[0], EXTENT[1], pixels, false)
+}
+
+fn read_rgba(path: &Path) -> io::Result<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path)?);
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "reference must be 8 bit RGBA"));
+    }
+
+    pixels.truncate(info.buffer_size());
+    Ok(pixels)
+}
@@ -4,11 +4,18 @@ mod application;
 mod ui;
 mod utils;
 mod mesh_gen;
+mod terrain;
+mod region;
+mod voxel;
+mod world;
+#[cfg(test)]
+mod golden;
 
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use once_cell::sync::Lazy;
-use rvkp::{init::Vk, vk_impl, vk_renderer::Renderer};
+use rvkp::{debug::DebugConfig, device::DevicePolicy, error::RvkpError, vk_impl, vk_renderer::Renderer};
 use winit::event_loop::EventLoop;
 
 /*
@@ -17,16 +24,93 @@ use winit::event_loop::EventLoop;
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 12)]
 async fn main() {
-    // mesh_gen::init().await;
+    mesh_gen::init();
     //event_loop::run().await;
 
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.iter().any(|a| a == "--list-gpus") {
+        print_gpus();
+        return;
+    }
+
+    // --world=<dir> picks the save directory, edits are written there when chunks unload and
+    // on exit
+    let world_dir = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--world="))
+        .unwrap_or(world::DEFAULT_WORLD_DIR);
+    match world::open_world(Path::new(world_dir), world::DEFAULT_RADIUS) {
+        Ok(opened) => *world::WORLD.lock().unwrap() = opened,
+        Err(e) => println!("failed to open the world in {world_dir}, edits won't be saved: {e}"),
+    }
+
     let event_loop = EventLoop::new();
 
-    let vk = vk_impl::VkImpl::new(&event_loop).await;
-    println!("vk initialized");
-    let renderer = Renderer::new(vk.clone()).await;
+    let renderer = match start(&event_loop, &args).await {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            println!("failed to start the renderer: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    event_loop::run(event_loop, renderer.clone()).await; // now we're talking
+}
+
+async fn start(event_loop: &EventLoop<()>, args: &[String]) -> Result<Arc<Mutex<Renderer>>, RvkpError> {
+    // --gpu=<policy> wins over RVKP_GPU, see DevicePolicy::parse
+    let mut policy = DevicePolicy::from_env_or(DevicePolicy::default());
+    if let Some(value) = args.iter().find_map(|a| a.strip_prefix("--gpu=")) {
+        match DevicePolicy::parse(value) {
+            Some(p) => policy = p,
+            None => println!("unknown gpu policy {value}, expected discrete, integrated, cpu, index:N or name:.."),
+        }
+    }
+
+    // --validation turns the layers on on top of whatever RVKP_VALIDATION asks for
+    let mut debug = DebugConfig::from_env();
+    if args.iter().any(|a| a == "--validation") {
+        debug.validation = true;
+    }
+
+    let vk = vk_impl::VkImpl::new(event_loop, policy, debug).await?;
+    println!("vk initialized on {}", vk.lock().unwrap().gpu.name);
+
+    // --vsync=on|off|adaptive, F9 cycles through them at runtime
+    if let Some(mode) = args.iter().find_map(|a| a.strip_prefix("--vsync=")) {
+        match vk_impl::Vsync::parse(mode) {
+            Some(vsync) => vk.lock().unwrap().vsync = vsync,
+            None => println!("unknown vsync mode {mode}, expected on, off or adaptive"),
+        }
+    }
+    let renderer = Renderer::new(vk.clone()).await?;
     println!("main renderer initialized");
-    vk.lock().unwrap().ignition(renderer.clone());
+    vk.lock().unwrap().ignition(renderer.clone())?;
     println!("ignited");
-    event_loop::run(event_loop, renderer.clone()).await; // now we're talking
+
+    Ok(renderer)
+}
+
+fn print_gpus() {
+    let gpus = match rvkp::device::list_gpus() {
+        Ok(gpus) => gpus,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+
+    for gpu in gpus {
+        println!(
+            "{}: {} ({:?}), vulkan {}, driver {} {}, {} MiB device memory",
+            gpu.index,
+            gpu.name,
+            gpu.device_type,
+            gpu.api_version,
+            gpu.driver_name.as_deref().unwrap_or("unknown"),
+            gpu.driver_info.as_deref().unwrap_or(""),
+            gpu.device_memory() / (1024 * 1024),
+        );
+    }
 }
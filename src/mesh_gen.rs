@@ -1,165 +1,784 @@
-use tokio::sync::{mpsc, Mutex};
-use tokio::task;
-
-use std::sync::{Arc, Mutex as StdMutex};
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex as StdMutex};
 
 use once_cell::sync::Lazy;
+use threadpool::ThreadPool;
 
 use crate::rvkp::mesh::Mesh;
-use crate::rvkp::init::Vk;
-use crate::rvkp::presenter::VkView;
+use crate::rvkp::vk_renderer::Renderer;
+
+use crate::utils::{idx_to_vec3, vec3_to_idx};
+use crate::voxel::{VoxelId, VoxelRegistry, AIR, VOXEL_TYPES};
+use crate::world::{ChunkCoord, ChunkLoadJob, WORLD};
+
+pub const CHUNK_SIZE: usize = 32;
+const MESH_WORKERS: usize = 8;
+
+// spins up the meshing workers
+pub fn init() {
+    Lazy::force(&MESH_POOL);
+}
 
-use crate::utils::{idx_to_vec3, vec3_to_idx, random};
+// queues loading of the chunks around the camera, inserts the ones the workers finished and
+// queues their meshes, and drops the meshes of unloaded chunks from the renderer. generating
+// and reading chunks happens on the workers, this only shuffles results around
+pub fn stream(camera_pos: Vec3A, renderer: &mut Renderer) {
+    MESH_POOL.set_camera(camera_pos);
 
-const CHUNK_SIZE: usize = 32;
-const WORLD_SIZE: usize = 32;
+    let mut world = WORLD.lock().unwrap();
+    let update = world.update(camera_pos);
 
-const CHUNKS: Lazy<Arc<StdMutex<Vec<bool>>>> = Lazy::new(|| {
-    let mut chunks = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
-    for _i in 0..WORLD_SIZE * WORLD_SIZE * WORLD_SIZE {
-        chunks.push(false);
+    for coord in update.unloaded {
+        MESH_POOL.cancel(coord);
+        renderer.remove_chunk_mesh(coord);
     }
 
-    Arc::new(StdMutex::new(chunks))
-});
+    for coord in update.load {
+        MESH_POOL.load(world.load_job(coord));
+    }
+
+    // neighbouring chunks finishing together would queue the same remesh several times
+    let mut remesh = HashSet::new();
+    for (coord, voxels) in MESH_POOL.take_loaded() {
+        remesh.extend(world.insert_chunk(coord, voxels));
+    }
+
+    for coord in remesh {
+        if let Some(job) = world.mesh_job(coord) {
+            MESH_POOL.submit(job);
+        }
+    }
+}
+
+// queues every chunk edited since the last call, their new meshes replace the old ones in
+// the renderer once uploaded
+pub fn remesh_dirty() {
+    let mut world = WORLD.lock().unwrap();
+
+    for coord in world.take_dirty() {
+        if let Some(job) = world.mesh_job(coord) {
+            MESH_POOL.submit(job);
+        }
+    }
+}
+
+// geometry of a chunk, built on the cpu and not uploaded yet
+pub struct CpuMesh {
+    pub coord: ChunkCoord,
+    pub verts: Vec<RVertex3d>,
+    pub inds: Vec<u32>,
+    generation: u64,
+}
+
+struct PoolState {
+    // keyed by chunk, so there is at most one job per chunk and this never grows past the
+    // number of chunks in range
+    pending: HashMap<ChunkCoord, (VoxelMeshGenJob, u64)>,
+    // generation of the newest job per chunk, results of older generations are thrown away
+    generations: HashMap<ChunkCoord, u64>,
+    // never reused, so a result from before a cancel can't match a later submit
+    next_generation: u64,
+    finished: HashMap<ChunkCoord, CpuMesh>,
+    // chunks to generate or read from disk, and their voxels once that's done
+    loads: HashMap<ChunkCoord, ChunkLoadJob>,
+    loaded: Vec<(ChunkCoord, Vec<VoxelId>)>,
+    camera: Vec3A,
+}
 
-pub async fn init() {
-    task::spawn(async {
+enum Work {
+    Mesh(VoxelMeshGenJob, u64),
+    Load(ChunkLoadJob),
+}
+
+// distance from the center of a chunk to `pos`
+fn chunk_distance(coord: ChunkCoord, pos: Vec3A) -> f32 {
+    let size = CHUNK_SIZE as f32;
+    let center = vec3a(coord[0] as f32 + 0.5, coord[1] as f32 + 0.5, coord[2] as f32 + 0.5) * size;
+
+    center.distance(pos)
+}
+
+// the key closest to the camera
+fn closest<T>(jobs: &HashMap<ChunkCoord, T>, camera: Vec3A) -> Option<(ChunkCoord, f32)> {
+    jobs.keys()
+        .map(|&coord| (coord, chunk_distance(coord, camera)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+struct PoolShared {
+    state: StdMutex<PoolState>,
+    job_ready: Condvar,
+}
+
+// fixed set of cpu workers loading and meshing chunks closest to the camera first. nothing here
+// touches the world or the renderer, results wait in the pool until take_loaded() and upload()
+// are called from the main thread
+pub struct MeshPool {
+    shared: Arc<PoolShared>,
+    workers: ThreadPool,
+}
+
+impl MeshPool {
+    pub fn new(workers: usize) -> Self {
+        let pool = Self::idle(workers);
+        for _ in 0..workers {
+            let shared = pool.shared.clone();
+            pool.workers.execute(move || Self::work(shared));
+        }
+
+        pool
+    }
+
+    // the pool before any worker takes jobs off it
+    fn idle(workers: usize) -> Self {
+        let shared = Arc::new(PoolShared {
+            state: StdMutex::new(PoolState {
+                pending: HashMap::new(),
+                generations: HashMap::new(),
+                next_generation: 0,
+                finished: HashMap::new(),
+                loads: HashMap::new(),
+                loaded: vec![],
+                camera: Vec3A::ZERO,
+            }),
+            job_ready: Condvar::new(),
+        });
+
+        Self {
+            shared,
+            workers: ThreadPool::new(workers),
+        }
+    }
+
+    fn work(shared: Arc<PoolShared>) {
         loop {
-            let receiver = &VOXGEN_CH.job_receiver;
-            let mut rcv_guard = receiver.lock().await;
+            let work = Self::next(&shared);
+            Self::run(&shared, work);
+        }
+    }
+
+    // does one job and stores its result. a job that panics loses its chunk but not the worker,
+    // the pool would shrink for good otherwise
+    fn run(shared: &PoolShared, work: Work) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match work {
+            Work::Load(job) => {
+                let voxels = job.run();
+                shared.state.lock().unwrap().loaded.push((job.coord, voxels));
+            }
+            Work::Mesh(job, generation) => {
+                let (verts, inds) = VoxelMeshGen::build(&job);
+
+                let mut state = shared.state.lock().unwrap();
+                if state.generations.get(&job.coord) == Some(&generation) {
+                    state.finished.insert(job.coord, CpuMesh {
+                        coord: job.coord,
+                        verts,
+                        inds,
+                        generation,
+                    });
+                }
+            }
+        }));
 
-            if let Some(rcv_result) = rcv_guard.recv().await {
-                let view = rcv_result.1;
-                let mut view_guard = view.lock().unwrap();
+        if result.is_err() {
+            println!("chunk job panicked, skipping it");
+        }
+    }
 
-                let vk = rcv_result.2;
-                let vk_guard = vk.lock().unwrap();
+    // blocks until there is work and takes the job closest to the camera, loads and meshes alike.
+    // picked at pop time so priorities follow the camera as it moves
+    fn next(shared: &PoolShared) -> Work {
+        let mut state = shared.state.lock().unwrap();
+        while state.pending.is_empty() && state.loads.is_empty() {
+            state = shared.job_ready.wait(state).unwrap();
+        }
 
-                let mesh = VoxelMeshGen::execute(rcv_result.0, &vk_guard);
+        let camera = state.camera;
+        let mesh = closest(&state.pending, camera);
+        let load = closest(&state.loads, camera);
 
-                // view_guard.meshes.clear();
-                view_guard.push_mesh(
-                    mesh
-                );
+        match (mesh, load) {
+            (Some((coord, a)), Some((_, b))) if a <= b => {
+                let (job, generation) = state.pending.remove(&coord).unwrap();
+                Work::Mesh(job, generation)
+            }
+            (_, Some((coord, _))) => Work::Load(state.loads.remove(&coord).unwrap()),
+            (Some((coord, _)), None) => {
+                let (job, generation) = state.pending.remove(&coord).unwrap();
+                Work::Mesh(job, generation)
             }
+            (None, None) => unreachable!(),
         }
-    });
-}
+    }
 
-pub struct VoxelGenChannel {
-    job_sender: mpsc::Sender<(VoxelMeshGenJob, Arc<StdMutex<VkView>>, Arc<StdMutex<Vk>>)>,
-    job_receiver: Arc<Mutex<mpsc::Receiver<(VoxelMeshGenJob, Arc<StdMutex<VkView>>, Arc<StdMutex<Vk>>)>>>,
-}
+    pub fn set_camera(&self, pos: Vec3A) {
+        self.shared.state.lock().unwrap().camera = pos;
+    }
+
+    // queues a job, replacing any job for the same chunk that hasn't finished yet
+    pub fn submit(&self, job: VoxelMeshGenJob) {
+        let mut state = self.shared.state.lock().unwrap();
+        let coord = job.coord;
+
+        state.next_generation += 1;
+        let generation = state.next_generation;
 
-impl VoxelGenChannel {
-    pub async fn send(
-        &self, 
-        job: VoxelMeshGenJob, 
-        view: Arc<StdMutex<VkView>>, 
-        vk: Arc<StdMutex<Vk>>,
-    ) {
-        self.job_sender.send((job, view, vk)).await.unwrap();
+        state.generations.insert(coord, generation);
+        state.pending.insert(coord, (job, generation));
+
+        self.shared.job_ready.notify_one();
     }
-}
 
-pub static VOXGEN_CH: Lazy<Arc<VoxelGenChannel>> = Lazy::new(|| {
-    let (job_sender, job_receiver) = mpsc::channel::<(VoxelMeshGenJob, Arc<StdMutex<VkView>>, Arc<StdMutex<Vk>>)>(100);
-    let job_receiver = Arc::new(Mutex::new(job_receiver));
+    // queues generating or reading a chunk, the voxels come back through take_loaded
+    pub fn load(&self, job: ChunkLoadJob) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.loads.insert(job.coord, job);
+
+        self.shared.job_ready.notify_one();
+    }
+
+    // forgets queued, running and finished work for a chunk
+    pub fn cancel(&self, coord: ChunkCoord) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        state.generations.remove(&coord);
+        state.pending.remove(&coord);
+        state.finished.remove(&coord);
+        state.loads.remove(&coord);
+        state.loaded.retain(|(c, _)| *c != coord);
+    }
+
+    // chunks whose voxels are ready since the last call
+    pub fn take_loaded(&self) -> Vec<(ChunkCoord, Vec<VoxelId>)> {
+        std::mem::take(&mut self.shared.state.lock().unwrap().loaded)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.shared.state.lock().unwrap().pending.len()
+    }
 
-    let channel = VoxelGenChannel {
-        job_sender,
-        job_receiver,
-    };
+    // meshes that finished since the last call and are still current
+    pub fn take_finished(&self) -> Vec<CpuMesh> {
+        let mut state = self.shared.state.lock().unwrap();
+        let finished = std::mem::take(&mut state.finished);
 
-    Arc::new(channel)
+        finished
+            .into_values()
+            .filter(|m| state.generations.get(&m.coord) == Some(&m.generation))
+            .collect()
+    }
+
+    // hands finished cpu meshes to the gpu, meant to be called once per frame from the render side
+    pub fn upload(&self, renderer: &mut Renderer) {
+        let vk_impl = renderer.vk_impl.clone();
+        let vk = vk_impl.lock().unwrap();
+
+        for mesh in self.take_finished() {
+            // all air, or completely buried
+            if mesh.inds.is_empty() {
+                renderer.remove_chunk_mesh(mesh.coord);
+                continue;
+            }
+
+            match Mesh::new(mesh.verts, mesh.inds, vec![], &vk) {
+                Ok(gpu_mesh) => renderer.set_chunk_mesh(mesh.coord, gpu_mesh),
+                // out of memory, the chunk stays invisible until it's meshed again
+                Err(e) => println!("failed to upload chunk {:?}: {e}", mesh.coord),
+            }
+        }
+
+        // one batch for every chunk that finished this frame
+        if let Err(e) = vk.uploader.clone().unwrap().flush() {
+            println!("failed to submit chunk uploads: {e}");
+        }
+    }
+}
+
+pub static MESH_POOL: Lazy<MeshPool> = Lazy::new(|| {
+    MeshPool::new(MESH_WORKERS)
 });
 
 use glam::{Vec3A, vec3a};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshingMode {
+    // all six faces of every solid voxel
+    Naive,
+    // only the faces that border air
+    #[default]
+    Culled,
+    // culled faces merged into larger coplanar quads
+    Greedy,
+}
+
 pub struct VoxelMeshGenJob {
-    voxels: Vec<bool>,
+    coord: ChunkCoord,
+    voxels: Arc<Vec<VoxelId>>,
     pos: Vec3A,
+    // voxels of the adjacent chunks, in the same order as FACES
+    neighbours: [Option<Arc<Vec<VoxelId>>>; 6],
+    mode: MeshingMode,
 }
 
 impl VoxelMeshGenJob {
-    pub fn chunk(x: f32, y: f32, z: f32) -> Option<Self> {
-        let chunk_idx = vec3_to_idx(x as usize, y as usize, z as usize, CHUNK_SIZE);
-        let mut voxels = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+    pub fn new(
+        coord: ChunkCoord,
+        voxels: Arc<Vec<VoxelId>>,
+        neighbours: [Option<Arc<Vec<VoxelId>>>; 6],
+    ) -> Self {
+        assert_eq!(voxels.len(), CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+        let size = CHUNK_SIZE as f32;
 
-        if CHUNKS.lock().unwrap()[chunk_idx] == true { 
-            return None 
+        Self {
+            coord,
+            voxels,
+            pos: vec3a(coord[0] as f32 * size, coord[1] as f32 * size, coord[2] as f32 * size),
+            neighbours,
+            mode: MeshingMode::default(),
         }
+    }
+
+    pub fn coord(&self) -> ChunkCoord {
+        self.coord
+    }
+
+    // job over a fixed set of voxels with no neighbouring chunks, e.g. for comparing modes
+    pub fn from_voxels(voxels: Vec<VoxelId>, pos: Vec3A) -> Self {
+        assert_eq!(voxels.len(), CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
 
-        for _i in 0..CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
-            voxels.push(random(0, 10) % 2 == 0); 
+        Self {
+            coord: crate::world::World::chunk_coord(pos),
+            voxels: Arc::new(voxels),
+            pos,
+            neighbours: Default::default(),
+            mode: MeshingMode::default(),
         }
-        CHUNKS.lock().unwrap()[chunk_idx] = true;
+    }
 
-        Some(Self {
-            voxels,
-            pos: vec3a(x * 32.0, y * 32.0, z * 32.0),
-        })
+    pub fn mode(mut self, mode: MeshingMode) -> Self {
+        self.mode = mode;
+
+        self
+    }
+
+    // the voxel at chunk-local (x, y, z); coordinates one step outside the chunk along `face`
+    // are looked up in that neighbour, missing neighbours count as air
+    fn voxel_at(&self, x: i32, y: i32, z: i32, face: usize) -> VoxelId {
+        let size = CHUNK_SIZE as i32;
+        let inside = |c: i32| c >= 0 && c < size;
+
+        if inside(x) && inside(y) && inside(z) {
+            return self.voxels[vec3_to_idx(x as usize, y as usize, z as usize, CHUNK_SIZE)];
+        }
+
+        match &self.neighbours[face] {
+            Some(neighbour) => {
+                let (x, y, z) = (x.rem_euclid(size), y.rem_euclid(size), z.rem_euclid(size));
+                neighbour[vec3_to_idx(x as usize, y as usize, z as usize, CHUNK_SIZE)]
+            }
+            None => AIR,
+        }
     }
 }
 
 
-struct VoxelMeshGen {}
+pub struct VoxelMeshGen {}
 
-use crate::rvkp::presenter::{vert, FVertex3d, InstanceData};
-use threadpool::ThreadPool;
+// (normal, corners) for each face of a unit voxel, corners wound counter-clockwise
+// when looking at the face from outside
+const FACES: [([i32; 3], [[f32; 3]; 4]); 6] = [
+    ([1, 0, 0],  [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 1.0]]), // right
+    ([-1, 0, 0], [[0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]]), // left
+    ([0, 1, 0],  [[0.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0]]), // top
+    ([0, -1, 0], [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]]), // bottom
+    ([0, 0, 1],  [[1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0], [0.0, 0.0, 1.0]]), // back
+    ([0, 0, -1], [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]]), // front
+];
+
+const FACE_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+// chunk offsets of the six neighbours, in the same order as FACES
+pub const NEIGHBOUR_OFFSETS: [[i32; 3]; 6] = [
+    FACES[0].0, FACES[1].0, FACES[2].0, FACES[3].0, FACES[4].0, FACES[5].0,
+];
+
+use crate::rvkp::vk_renderer::RVertex3d;
 impl VoxelMeshGen {
-    pub fn execute(job: VoxelMeshGenJob, vk: &Vk) -> Mesh {
-        let voxels = job.voxels;
-        let pool = ThreadPool::new(12);
+    // cpu side of the mesher, produces chunk-local indexed geometry in world space
+    pub fn build(job: &VoxelMeshGenJob) -> (Vec<RVertex3d>, Vec<u32>) {
+        assert_eq!(job.voxels.len(), CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
 
+        let types = VOXEL_TYPES.read().unwrap();
+
+        match job.mode {
+            MeshingMode::Naive => Self::build_per_voxel(job, &types, false),
+            MeshingMode::Culled => Self::build_per_voxel(job, &types, true),
+            MeshingMode::Greedy => Self::build_greedy(job, &types),
+        }
+    }
+
+    // triangle count of the same chunk under every meshing mode
+    pub fn triangle_counts(job: &VoxelMeshGenJob) -> [(MeshingMode, usize); 3] {
+        [MeshingMode::Naive, MeshingMode::Culled, MeshingMode::Greedy].map(|mode| {
+            let job = VoxelMeshGenJob {
+                coord: job.coord,
+                voxels: job.voxels.clone(),
+                pos: job.pos,
+                neighbours: job.neighbours.clone(),
+                mode,
+            };
+
+            (mode, Self::build(&job).1.len() / 3)
+        })
+    }
+
+    fn build_per_voxel(
+        job: &VoxelMeshGenJob,
+        types: &VoxelRegistry,
+        cull: bool,
+    ) -> (Vec<RVertex3d>, Vec<u32>) {
         let mut verts = vec![];
         let mut inds = vec![];
-        let instcs = vec![InstanceData {
-            ofs: [0.0, 0.0, 0.0], fun_factor: [0.0, 0.0, 0.0] 
-        }];
 
-        assert_eq!(voxels.len(), CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+        for i in 0..job.voxels.len() {
+            let voxel = job.voxels[i];
+            if voxel == AIR { continue }
+            let color = types.get(voxel).color;
+            let local = idx_to_vec3(i, CHUNK_SIZE);
+            // job.pos is already the chunk's world-space origin
+            let voxel_coord = local + job.pos;
+
+            for (face, (normal, corners)) in FACES.iter().enumerate() {
+                // faces covered by an opaque voxel are never visible
+                if cull && !types.face_visible(voxel, job.voxel_at(
+                    local.x as i32 + normal[0],
+                    local.y as i32 + normal[1],
+                    local.z as i32 + normal[2],
+                    face,
+                )) {
+                    continue;
+                }
 
-        let chunk_size = CHUNK_SIZE as f32;
+                push_quad(&mut verts, &mut inds, voxel_coord, corners, [1.0, 1.0, 1.0], color);
+            }
+        }
 
-        for i in 0..voxels.len() {
-            if voxels[i] == false { continue }
-            let voxel_coord = idx_to_vec3(i, CHUNK_SIZE) + job.pos * chunk_size;
+        (verts, inds)
+    }
 
-            for &dx in &[0.0, 1.0] {
-                for &dy in &[0.0, 1.0] {
-                    for &dz in &[0.0, 1.0] {
-                        let x = voxel_coord.x + dx;
-                        let y = voxel_coord.y + dy;
-                        let z = voxel_coord.z + dz;
+    // sweeps every slice of the chunk once per face direction, building a mask of the visible
+    // faces in that slice and covering runs of the same material with as few rectangles as possible
+    fn build_greedy(job: &VoxelMeshGenJob, types: &VoxelRegistry) -> (Vec<RVertex3d>, Vec<u32>) {
+        let mut verts = vec![];
+        let mut inds = vec![];
 
-                        verts.push(vert(x, y, z))
+        let n = CHUNK_SIZE;
+        // material of the visible face at each cell of the slice, AIR where there is none
+        let mut mask = vec![AIR; n * n];
+
+        for (face, (normal, corners)) in FACES.iter().enumerate() {
+            // d is the axis the face points along, u and v span the slice
+            let d = normal.iter().position(|&c| c != 0).unwrap();
+            let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+
+            for layer in 0..n {
+                for j in 0..n {
+                    for i in 0..n {
+                        let mut p = [0i32; 3];
+                        p[d] = layer as i32;
+                        p[u] = i as i32;
+                        p[v] = j as i32;
+
+                        let voxel = job.voxels[vec3_to_idx(p[0] as usize, p[1] as usize, p[2] as usize, n)];
+                        let visible = types.face_visible(voxel, job.voxel_at(
+                            p[0] + normal[0],
+                            p[1] + normal[1],
+                            p[2] + normal[2],
+                            face,
+                        ));
+                        mask[j * n + i] = if visible { voxel } else { AIR };
+                    }
+                }
+
+                for j in 0..n {
+                    let mut i = 0;
+                    while i < n {
+                        let voxel = mask[j * n + i];
+                        if voxel == AIR {
+                            i += 1;
+                            continue;
+                        }
+
+                        let mut w = 1;
+                        while i + w < n && mask[j * n + i + w] == voxel {
+                            w += 1;
+                        }
+
+                        let mut h = 1;
+                        while j + h < n && (i..i + w).all(|k| mask[(j + h) * n + k] == voxel) {
+                            h += 1;
+                        }
+
+                        for jj in j..j + h {
+                            for k in i..i + w {
+                                mask[jj * n + k] = AIR;
+                            }
+                        }
+
+                        let mut origin = [0.0; 3];
+                        origin[d] = layer as f32;
+                        origin[u] = i as f32;
+                        origin[v] = j as f32;
+
+                        let mut size = [1.0; 3];
+                        size[u] = w as f32;
+                        size[v] = h as f32;
+
+                        push_quad(
+                            &mut verts,
+                            &mut inds,
+                            Vec3A::from(origin) + job.pos,
+                            corners,
+                            size,
+                            types.get(voxel).color,
+                        );
+
+                        i += w;
                     }
                 }
-            }
-            let base_index = (voxel_coord.x * chunk_size * chunk_size + voxel_coord.y * chunk_size + voxel_coord.z) as u32 * 8;
-
-            let idx = [ 
-                [0, 1, 2, 1, 2, 3], // front
-                [4, 5, 6, 5, 6, 7], // back
-                [0, 1, 4, 1, 4, 5], // bottom
-                [2, 3, 6, 3, 6, 7], // top
-                [0, 2, 4, 2, 4, 6], // left
-                [1, 3, 5, 3, 5, 7], // right
-            ];
-
-            let idx_vec = idx.to_vec();
-
-            for i in 0..idx_vec.len() {
-                let face = idx_vec[i];
-                // if self.adjacent_voxs[i] == false {
-                    inds.extend(face.iter().map(|&j| base_index + j));
-                // }
             }
         }
 
-        //let mesh = Mesh::new(verts, inds, instcs, &vk);
+        (verts, inds)
+    }
+}
+
+// appends one face, the unit face `corners` stretched by `size` and placed at `origin`, with its
+// material color on every vertex
+fn push_quad(
+    verts: &mut Vec<RVertex3d>,
+    inds: &mut Vec<u32>,
+    origin: Vec3A,
+    corners: &[[f32; 3]; 4],
+    size: [f32; 3],
+    color: [f32; 3],
+) {
+    // indices are local to this chunk's vertex buffer
+    let base_index = verts.len() as u32;
+
+    for corner in corners {
+        verts.push(RVertex3d::colored(
+            origin.x + corner[0] * size[0],
+            origin.y + corner[1] * size[1],
+            origin.z + corner[2] * size[2],
+            color,
+        ));
+    }
+
+    inds.extend(FACE_INDICES.iter().map(|&j| base_index + j));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{DIRT, STONE};
+    use crate::world::World;
+
+    const VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+    // [naive, culled, greedy] triangle counts of a chunk with no neighbours
+    fn counts(voxels: Vec<VoxelId>) -> [usize; 3] {
+        let job = VoxelMeshGenJob::from_voxels(voxels, Vec3A::ZERO);
+        VoxelMeshGen::triangle_counts(&job).map(|(_, n)| n)
+    }
+
+    // same, but with the adjacent chunks in FACES order
+    fn counts_between(voxels: Vec<VoxelId>, neighbours: [Option<Vec<VoxelId>>; 6]) -> [usize; 3] {
+        let job = VoxelMeshGenJob::new([0, 0, 0], Arc::new(voxels), neighbours.map(|n| n.map(Arc::new)));
+        VoxelMeshGen::triangle_counts(&job).map(|(_, n)| n)
+    }
+
+    fn chunk_with(f: impl Fn(usize, usize, usize) -> VoxelId) -> Vec<VoxelId> {
+        (0..VOLUME)
+            .map(|i| {
+                let p = idx_to_vec3(i, CHUNK_SIZE);
+                f(p.x as usize, p.y as usize, p.z as usize)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_voxel() {
+        let voxels = chunk_with(|x, y, z| if [x, y, z] == [5, 6, 7] { STONE } else { AIR });
+
+        assert_eq!(counts(voxels), [12, 12, 12]);
+    }
+
+    #[test]
+    fn full_chunk() {
+        let n = CHUNK_SIZE;
+
+        assert_eq!(counts(vec![STONE; VOLUME]), [6 * n * n * n * 2, 6 * n * n * 2, 12]);
+    }
+
+    #[test]
+    fn surrounded_chunk_has_no_faces() {
+        let n = CHUNK_SIZE;
+        let full = || Some(vec![STONE; VOLUME]);
+
+        let counts = counts_between(vec![STONE; VOLUME], [full(), full(), full(), full(), full(), full()]);
+        assert_eq!(counts, [6 * n * n * n * 2, 0, 0]);
+    }
+
+    #[test]
+    fn neighbour_hides_only_its_side() {
+        let n = CHUNK_SIZE;
+        // only the +x neighbour is loaded, the other sides count as air
+        let neighbours = [Some(vec![STONE; VOLUME]), None, None, None, None, None];
+
+        assert_eq!(counts_between(vec![STONE; VOLUME], neighbours), [6 * n * n * n * 2, 5 * n * n * 2, 5 * 2]);
+    }
+
+    #[test]
+    fn border_voxel_looks_into_the_neighbour() {
+        let last = CHUNK_SIZE - 1;
+        let voxels = chunk_with(|x, y, z| if [x, y, z] == [last, 3, 4] { STONE } else { AIR });
+
+        // the neighbour's voxel right across the border hides the +x face
+        let across = chunk_with(|x, y, z| if [x, y, z] == [0, 3, 4] { STONE } else { AIR });
+        assert_eq!(counts_between(voxels.clone(), [Some(across), None, None, None, None, None]), [12, 10, 10]);
+
+        // one voxel off doesn't
+        let beside = chunk_with(|x, y, z| if [x, y, z] == [0, 4, 4] { STONE } else { AIR });
+        assert_eq!(counts_between(voxels, [Some(beside), None, None, None, None, None]), [12, 12, 12]);
+    }
+
+    #[test]
+    fn checkerboard() {
+        let voxels = chunk_with(|x, y, z| if (x + y + z) % 2 == 0 { STONE } else { AIR });
+        let [naive, culled, greedy] = counts(voxels);
+
+        assert_eq!(culled, naive);
+        assert_eq!(greedy, culled);
+    }
+
+    #[test]
+    fn greedy_keeps_materials_apart() {
+        // a 2x1x1 bar, once in one material and once split between two
+        let bar = |right| chunk_with(move |x, y, z| match [x, y, z] {
+            [0, 0, 0] => STONE,
+            [1, 0, 0] => right,
+            _ => AIR,
+        });
+
+        assert_eq!(counts(bar(STONE))[2], 12);
+        // the shared face is hidden either way, but the four long sides can't be merged
+        assert_eq!(counts(bar(DIRT)), [24, 20, 20]);
+    }
+
+    // POOL //
+    //
+    // the pools below have no workers, the tests take and run the jobs themselves
+
+    fn job(coord: ChunkCoord, voxel: VoxelId) -> VoxelMeshGenJob {
+        VoxelMeshGenJob::new(coord, Arc::new(vec![voxel; VOLUME]), Default::default())
+    }
+
+    fn next_coord(pool: &MeshPool) -> ChunkCoord {
+        match MeshPool::next(&pool.shared) {
+            Work::Mesh(job, _) => job.coord,
+            Work::Load(job) => job.coord,
+        }
+    }
+
+    #[test]
+    fn pool_discards_outdated_results() {
+        let pool = MeshPool::idle(1);
+        pool.submit(job([0, 0, 0], STONE));
+        let old = MeshPool::next(&pool.shared);
+
+        // the chunk changed while the old job was running
+        pool.submit(job([0, 0, 0], AIR));
+        MeshPool::run(&pool.shared, old);
+        assert!(pool.take_finished().is_empty());
+
+        MeshPool::run(&pool.shared, MeshPool::next(&pool.shared));
+        let finished = pool.take_finished();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].coord, [0, 0, 0]);
+        assert!(finished[0].inds.is_empty());
+    }
+
+    #[test]
+    fn pool_replaces_pending_jobs() {
+        let pool = MeshPool::idle(1);
+        pool.submit(job([0, 0, 0], AIR));
+        pool.submit(job([0, 0, 0], STONE));
+        assert_eq!(pool.pending(), 1);
+
+        // only the newer job is left, and it's the full chunk
+        MeshPool::run(&pool.shared, MeshPool::next(&pool.shared));
+        assert_eq!(pool.pending(), 0);
+
+        let finished = pool.take_finished();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].inds.len(), 6 * CHUNK_SIZE * CHUNK_SIZE * 2 * 3);
+    }
+
+    #[test]
+    fn pool_cancel_forgets_everything() {
+        let pool = MeshPool::idle(1);
+        pool.submit(job([0, 0, 0], STONE));
+        let running = MeshPool::next(&pool.shared);
+        pool.submit(job([1, 0, 0], STONE));
+        pool.load(World::new(0).load_job([2, 0, 0]));
+
+        pool.cancel([0, 0, 0]);
+        pool.cancel([1, 0, 0]);
+        pool.cancel([2, 0, 0]);
+        assert_eq!(pool.pending(), 0);
+        assert!(pool.shared.state.lock().unwrap().loads.is_empty());
+
+        // resubmitting gets a fresh generation, so the job from before the cancel still loses
+        pool.submit(job([0, 0, 0], AIR));
+        MeshPool::run(&pool.shared, running);
+        assert!(pool.take_finished().is_empty());
+
+        MeshPool::run(&pool.shared, MeshPool::next(&pool.shared));
+        assert_eq!(pool.take_finished().len(), 1);
+    }
+
+    #[test]
+    fn pool_takes_the_closest_job_first() {
+        let pool = MeshPool::idle(1);
+        // in the middle of chunk [2, 0, 0]
+        pool.set_camera(vec3a(2.5, 0.5, 0.5) * CHUNK_SIZE as f32);
+
+        for coord in [[0, 0, 0], [5, 0, 0], [2, 0, 0], [2, 1, 0]] {
+            pool.submit(job(coord, AIR));
+        }
+        pool.load(World::new(0).load_job([3, 0, 0]));
+        pool.load(World::new(0).load_job([9, 0, 0]));
+
+        let order: Vec<_> = (0..6).map(|_| next_coord(&pool)).collect();
+        assert_eq!(order[..2], [[2, 0, 0], [2, 1, 0]]);
+        // loads and meshes are picked from the same distance order
+        assert_eq!(order[2..], [[3, 0, 0], [0, 0, 0], [5, 0, 0], [9, 0, 0]]);
+    }
+
+    #[test]
+    fn pool_survives_a_panicking_job() {
+        let pool = MeshPool::idle(1);
+        let mut broken = job([0, 0, 0], STONE);
+        broken.voxels = Arc::new(vec![]);
+
+        pool.submit(broken);
+        MeshPool::run(&pool.shared, MeshPool::next(&pool.shared));
+        assert!(pool.take_finished().is_empty());
 
-        todo!()
+        pool.submit(job([0, 0, 0], STONE));
+        MeshPool::run(&pool.shared, MeshPool::next(&pool.shared));
+        assert_eq!(pool.take_finished().len(), 1);
     }
 }
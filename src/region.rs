@@ -0,0 +1,323 @@
+// on-disk chunk storage. chunks are grouped into regions of REGION_SIZE³ chunks, one file each:
+//
+//   header   magic "AREG", format version (u16), chunk size (u16)
+//   table    REGION_VOLUME entries of (offset u32, length u32), zero length = chunk not stored
+//   payloads one per stored chunk, see encode_chunk
+//
+// everything is little endian
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::mesh_gen::CHUNK_SIZE;
+use crate::utils::vec3_to_idx;
+use crate::voxel::VoxelId;
+use crate::world::ChunkCoord;
+
+pub const FORMAT_VERSION: u16 = 1;
+pub const REGION_SIZE: i32 = 8;
+
+const MAGIC: &[u8; 4] = b"AREG";
+const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+const HEADER_LEN: usize = 4 + 2 + 2;
+const TABLE_LEN: usize = REGION_VOLUME * 8;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn region_coord(chunk: ChunkCoord) -> [i32; 3] {
+    chunk.map(|c| c.div_euclid(REGION_SIZE))
+}
+
+fn slot(chunk: ChunkCoord) -> usize {
+    let [x, y, z] = chunk.map(|c| c.rem_euclid(REGION_SIZE) as usize);
+    vec3_to_idx(x, y, z, REGION_SIZE as usize)
+}
+
+pub fn region_path(dir: &Path, region: [i32; 3]) -> PathBuf {
+    dir.join(format!("r.{}.{}.{}.region", region[0], region[1], region[2]))
+}
+
+// palette of the distinct ids in the chunk followed by run-length encoded palette indices:
+//
+//   palette length (u16), palette ids (u16 each)
+//   runs of (count u16, palette index u16) until CHUNK_SIZE³ voxels are covered
+pub fn encode_chunk(voxels: &[VoxelId]) -> Vec<u8> {
+    let mut palette: Vec<VoxelId> = vec![];
+    let mut lookup = HashMap::new();
+    for &v in voxels {
+        lookup.entry(v).or_insert_with(|| {
+            palette.push(v);
+            palette.len() as u16 - 1
+        });
+    }
+
+    let mut out = vec![];
+    out.extend((palette.len() as u16).to_le_bytes());
+    for id in &palette {
+        out.extend(id.to_le_bytes());
+    }
+
+    let mut i = 0;
+    while i < voxels.len() {
+        let v = voxels[i];
+        let mut run = 1;
+        while i + run < voxels.len() && voxels[i + run] == v && run < u16::MAX as usize {
+            run += 1;
+        }
+
+        out.extend((run as u16).to_le_bytes());
+        out.extend(lookup[&v].to_le_bytes());
+        i += run;
+    }
+
+    out
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> io::Result<Vec<VoxelId>> {
+    let volume = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+    let mut words = bytes
+        .chunks_exact(2)
+        .map(|w| u16::from_le_bytes([w[0], w[1]]));
+    let mut next = || words.next().ok_or_else(|| invalid("truncated chunk"));
+
+    let palette_len = next()? as usize;
+    let palette = (0..palette_len).map(|_| next()).collect::<io::Result<Vec<_>>>()?;
+
+    let mut voxels = Vec::with_capacity(volume);
+    while voxels.len() < volume {
+        let run = next()? as usize;
+        let id = *palette.get(next()? as usize).ok_or_else(|| invalid("bad palette index"))?;
+
+        if run == 0 || voxels.len() + run > volume {
+            return Err(invalid("bad run length"));
+        }
+        voxels.extend(std::iter::repeat_n(id, run));
+    }
+
+    Ok(voxels)
+}
+
+// the encoded chunks of one region file
+pub struct Region {
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self { chunks: vec![None; REGION_VOLUME] }
+    }
+
+    fn read_header(file: &mut File) -> io::Result<Vec<(u32, u32)>> {
+        let mut header = [0u8; HEADER_LEN];
+        file.read_exact(&mut header)?;
+
+        if &header[0..4] != MAGIC {
+            return Err(invalid("not a region file"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != FORMAT_VERSION {
+            return Err(invalid(&format!("unsupported region format version {version}")));
+        }
+        let chunk_size = u16::from_le_bytes([header[6], header[7]]);
+        if chunk_size as usize != CHUNK_SIZE {
+            return Err(invalid(&format!("region was saved with chunk size {chunk_size}")));
+        }
+
+        let mut table = vec![0u8; TABLE_LEN];
+        file.read_exact(&mut table)?;
+
+        Ok(table
+            .chunks_exact(8)
+            .map(|e| (
+                u32::from_le_bytes([e[0], e[1], e[2], e[3]]),
+                u32::from_le_bytes([e[4], e[5], e[6], e[7]]),
+            ))
+            .collect())
+    }
+
+    // whole region, empty if the file doesn't exist yet
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+
+        let table = Self::read_header(&mut file)?;
+        let mut region = Self::new();
+
+        for (i, &(offset, len)) in table.iter().enumerate() {
+            if len == 0 { continue }
+
+            let mut payload = vec![0u8; len as usize];
+            file.seek(SeekFrom::Start(offset as u64))?;
+            file.read_exact(&mut payload)?;
+            region.chunks[i] = Some(payload);
+        }
+
+        Ok(region)
+    }
+
+    // only reads the header and the one chunk, this is what streaming uses
+    pub fn read_chunk(path: &Path, chunk: ChunkCoord) -> io::Result<Option<Vec<VoxelId>>> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let (offset, len) = Self::read_header(&mut file)?[slot(chunk)];
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let mut payload = vec![0u8; len as usize];
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(&mut payload)?;
+
+        decode_chunk(&payload).map(Some)
+    }
+
+    pub fn set(&mut self, chunk: ChunkCoord, voxels: &[VoxelId]) {
+        self.chunks[slot(chunk)] = Some(encode_chunk(voxels));
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_LEN + TABLE_LEN);
+        header.extend(MAGIC);
+        header.extend(FORMAT_VERSION.to_le_bytes());
+        header.extend((CHUNK_SIZE as u16).to_le_bytes());
+
+        let mut offset = (HEADER_LEN + TABLE_LEN) as u32;
+        for chunk in &self.chunks {
+            let len = chunk.as_ref().map_or(0, |c| c.len() as u32);
+            header.extend((if len == 0 { 0 } else { offset }).to_le_bytes());
+            header.extend(len.to_le_bytes());
+            offset += len;
+        }
+
+        // write next to the old file and swap, so a crash never leaves half a region behind
+        let tmp = path.with_extension("region.tmp");
+        {
+            let mut file = io::BufWriter::new(File::create(&tmp)?);
+            file.write_all(&header)?;
+            for chunk in self.chunks.iter().flatten() {
+                file.write_all(chunk)?;
+            }
+            file.flush()?;
+        }
+
+        fs::rename(tmp, path)
+    }
+}
+
+pub fn load_chunk(dir: &Path, chunk: ChunkCoord) -> io::Result<Option<Vec<VoxelId>>> {
+    Region::read_chunk(&region_path(dir, region_coord(chunk)), chunk)
+}
+
+// writes the given chunks into their region files, keeping whatever else those files hold
+pub fn save_chunks<'a>(
+    dir: &Path,
+    chunks: impl IntoIterator<Item = (ChunkCoord, &'a [VoxelId])>,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut regions: HashMap<[i32; 3], Vec<(ChunkCoord, &[VoxelId])>> = HashMap::new();
+    for (coord, voxels) in chunks {
+        regions.entry(region_coord(coord)).or_default().push((coord, voxels));
+    }
+
+    for (region_coord, chunks) in regions {
+        let path = region_path(dir, region_coord);
+        let mut region = Region::read(&path)?;
+
+        for (coord, voxels) in chunks {
+            region.set(coord, voxels);
+        }
+
+        region.write(&path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+    fn mixed() -> Vec<VoxelId> {
+        (0..VOLUME).map(|i| ((i / 7) % 5) as VoxelId).collect()
+    }
+
+    // fresh directory under the system temp dir
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arendp-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn encode_uniform() {
+        let voxels = vec![3; VOLUME];
+        let bytes = encode_chunk(&voxels);
+
+        // one palette entry and a few runs as long as a u16 allows
+        assert!(bytes.len() <= 2 + 2 + 4 * (VOLUME / u16::MAX as usize + 1));
+        assert_eq!(decode_chunk(&bytes).unwrap(), voxels);
+    }
+
+    #[test]
+    fn encode_mixed() {
+        let voxels = mixed();
+
+        assert_eq!(decode_chunk(&encode_chunk(&voxels)).unwrap(), voxels);
+    }
+
+    #[test]
+    fn encode_max_palette() {
+        // every voxel different, the palette is as long as the chunk
+        let voxels: Vec<VoxelId> = (0..VOLUME).map(|i| (VOLUME - 1 - i) as VoxelId).collect();
+
+        assert_eq!(decode_chunk(&encode_chunk(&voxels)).unwrap(), voxels);
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        let bytes = encode_chunk(&mixed());
+
+        assert!(decode_chunk(&bytes[..bytes.len() - 2]).is_err());
+        assert!(decode_chunk(&[1, 0, 0, 0, 1, 0, 5, 0]).is_err());
+    }
+
+    #[test]
+    fn region_round_trip() {
+        let dir = temp_dir("region");
+        let path = region_path(&dir, [-1, 0, 0]);
+        let (a, b) = ([-1, 0, 0], [-8, 7, 3]);
+
+        let mut region = Region::new();
+        region.set(a, &vec![1; VOLUME]);
+        region.set(b, &mixed());
+        region.write(&path).unwrap();
+
+        assert_eq!(Region::read_chunk(&path, a).unwrap(), Some(vec![1; VOLUME]));
+        assert_eq!(Region::read_chunk(&path, b).unwrap(), Some(mixed()));
+        assert_eq!(Region::read_chunk(&path, [-2, 0, 0]).unwrap(), None);
+
+        // rewriting keeps the chunks that weren't touched
+        save_chunks(&dir, [(a, vec![2; VOLUME].as_slice())]).unwrap();
+        assert_eq!(load_chunk(&dir, a).unwrap(), Some(vec![2; VOLUME]));
+        assert_eq!(load_chunk(&dir, b).unwrap(), Some(mixed()));
+        assert_eq!(load_chunk(&dir, [40, 0, 0]).unwrap(), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,21 +1,29 @@
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
 
 use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
 use vulkano::format::Format;
 
+use crate::rvkp::debug;
+use crate::rvkp::error::RvkpError;
+use crate::rvkp::upload::UploadTicket;
+use crate::rvkp::vk_impl::VkImpl;
+use crate::rvkp::vk_renderer::InstanceData;
 
-impl crate::rvkp::init::Vk {
+impl VkImpl {
+    // host visible buffer, for copying things in and out of the gpu
     pub fn buf_iter
         <T: Sync + Send + Sized + ExactSizeIterator>
-        (&self, iter: T) -> vulkano::buffer::Subbuffer<[<T as Iterator>::Item]> 
+        (&self, iter: T) -> Result<Subbuffer<[<T as Iterator>::Item]>, RvkpError> 
         where 
             <T as Iterator>::Item: bytemuck::Pod, 
             <T as Iterator>::Item: Sync, 
             <T as Iterator>::Item: Send  
     {
-        Buffer::from_iter(
-            self.mem_allocators.memory_allocator.clone(),
+        let buffer = Buffer::from_iter(
+            self.allocators.clone().unwrap().memory.clone(),
             BufferCreateInfo {
                 usage: BufferUsage::TRANSFER_DST,
                 ..Default::default()
@@ -26,69 +34,30 @@ impl crate::rvkp::init::Vk {
                 ..Default::default()
             },
             iter,
-        ).expect("failed to create buffer")
+        )?;
+        debug::set_name(&self.device, &**buffer.buffer(), "host buffer");
+
+        Ok(buffer)
     }
 
-    pub fn vertex_buffer(&self, vec: Vec<crate::rvkp::presenter::FVertex3d>)
-    -> vulkano::buffer::Subbuffer<[crate::rvkp::presenter::FVertex3d]> {
-        Buffer::from_iter(
-            self.mem_allocators.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::VERTEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            vec,
-        )
-        .expect("failed to create buffer")
+    // device local, filled by the uploader with its next flush. meshes hold on to the returned
+    // upload ticket and aren't drawn before it's done
+    pub fn vertex_buffer<V: BufferContents>(&self, vertices: Vec<V>) -> Result<(Subbuffer<[V]>, UploadTicket), RvkpError> {
+        self.uploader.clone().unwrap().buffer(vertices, BufferUsage::VERTEX_BUFFER, "vertex buffer")
     }
-    
-    pub fn index_buffer(&self, vec: Vec<u32>)
-    -> vulkano::buffer::Subbuffer<[u32]> {
-        Buffer::from_iter(
-            self.mem_allocators.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::INDEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            vec,
-        )
-        .expect("failed to create buffer")
+
+    // per instance vertex data, bound next to the vertex buffer
+    pub fn instance_buffer(&self, instances: Vec<InstanceData>) -> Result<(Subbuffer<[InstanceData]>, UploadTicket), RvkpError> {
+        self.vertex_buffer(instances)
     }
 
-    pub fn instance_buffer(
-        &self, 
-        i_data: Vec<crate::rvkp::presenter::InstanceData>
-    ) -> vulkano::buffer::Subbuffer<[crate::rvkp::presenter::InstanceData]> 
-    {
-        Buffer::from_iter(
-            self.mem_allocators.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::VERTEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            i_data,
-        )
-        .expect("failed to create buffer")
+    pub fn index_buffer(&self, indices: Vec<u32>) -> Result<(Subbuffer<[u32]>, UploadTicket), RvkpError> {
+        self.uploader.clone().unwrap().buffer(indices, BufferUsage::INDEX_BUFFER, "index buffer")
     }
 
-    pub fn image(&self, dim: [u32; 3]) -> std::sync::Arc<Image>  {
-        Image::new(
-            self.mem_allocators.memory_allocator.clone(),
+    pub fn image(&self, dim: [u32; 3]) -> Result<Arc<Image>, RvkpError> {
+        let image = Image::new(
+            self.allocators.clone().unwrap().memory.clone(),
             ImageCreateInfo {
                 image_type: vulkano::image::ImageType::Dim2d,
                 format: Format::R8G8B8A8_UNORM,
@@ -103,7 +72,9 @@ impl crate::rvkp::init::Vk {
                 memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
                 ..Default::default()
             },
-        )
-        .unwrap()
+        )?;
+        debug::set_name(&self.device, &*image, "image");
+
+        Ok(image)
     }
 }
@@ -7,7 +7,6 @@ use vulkano::pipeline::layout::PipelineLayout;
 use std::sync::Arc;
 
 
-use crate::rvkp::init::Vk;
 use crate::rvkp::shader::vs::PushConstantCameraData;
 
 use crate::utils::random;
@@ -15,11 +14,24 @@ use crate::utils::random;
 const UP: Vec3A = vec3a(0.0, 1.0, 0.0);
 const SPEED: f32 = 0.5;
 const SENSITIVITY: f32 = 0.1;
+const FOV: f32 = 70.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 1000.0;
+
+// right handed like the look_at_rh view, with y flipped because vulkan's clip space points down
+fn projection(aspect: f32) -> Mat4 {
+    let mut proj = Mat4::perspective_rh(FOV.to_radians(), aspect, NEAR, FAR);
+    proj.y_axis.y *= -1.0;
+
+    proj
+}
 
 #[derive(Debug, Clone)]
 pub struct Camera {
     pub proj: Mat4,
     pub view: Mat4,
+    // width / height of the images rendered to
+    aspect: f32,
 
     pub pos: Vec3A,
     target: Vec3A,
@@ -43,7 +55,8 @@ pub struct Camera {
 
 impl Camera {
     pub fn new() -> Self {
-        let (pitch, yaw): (f32, f32) = (0.0, 270.0);
+        // looking down +z, at the origin
+        let (pitch, yaw): (f32, f32) = (0.0, 90.0);
         let pos = vec3a(0.0, 0.0, -1.0);
         let target = vec3a(0.0, 0.0, -1.0);
         let mut direction = Vec3A::normalize(pos - target);
@@ -61,16 +74,14 @@ impl Camera {
             up.into(),
         );
 
-        let proj = Mat4::perspective_lh(
-            70.0_f32.to_radians(),
-            1.0, 
-            0.1, 
-            1000.0,
-        );
+        // corrected by set_extent once the size of the render target is known
+        let aspect = 1.0;
+        let proj = projection(aspect);
 
         Self {
             proj,
             view, 
+            aspect,
 
             pos,
             target,
@@ -93,17 +104,34 @@ impl Camera {
         }
     }
 
+    // keeps the projection's aspect ratio in line with the render target, zero sizes are ignored
+    pub fn set_extent(&mut self, extent: [u32; 2]) {
+        if extent.contains(&0) {
+            return;
+        }
+
+        let aspect = extent[0] as f32 / extent[1] as f32;
+        if aspect != self.aspect {
+            self.aspect = aspect;
+            self.proj = projection(aspect);
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
     pub fn update(&mut self) {
         self.dt = 0.0016;
 
         if self.keymap[0] {
-            self.pos -= SPEED * self.dt * self.front;
+            self.pos += SPEED * self.dt * self.front;
         }
         if self.keymap[1] {
             self.pos -= SPEED * self.dt * Vec3A::cross(self.front, self.up);
         }
         if self.keymap[2] {
-            self.pos += SPEED * self.dt * self.front;
+            self.pos -= SPEED * self.dt * self.front;
         }
         if self.keymap[3] {
             self.pos += SPEED * self.dt * Vec3A::cross(self.front, self.up);
@@ -197,8 +225,9 @@ impl Camera {
     {
         builder
             .push_constants(layout.clone(), 0, PushConstantCameraData {
-                proj: self.proj.to_cols_array_2d(),
-                view: self.view.to_cols_array_2d(),
+                view_proj: (self.proj * self.view).to_cols_array_2d(),
+                // overwritten by every mesh that gets drawn
+                model: Mat4::IDENTITY.to_cols_array_2d(),
             })
             .unwrap();
 
@@ -206,3 +235,51 @@ impl Camera {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn looking_at(pos: Vec3A, target: Vec3A) -> Camera {
+        let mut camera = Camera::new();
+        camera.pos = pos;
+        camera.front = (target - pos).normalize();
+        camera.update();
+
+        camera
+    }
+
+    // normalized device coordinates of `p`, None if it gets clipped
+    fn ndc(camera: &Camera, p: Vec3A) -> Option<Vec3> {
+        let clip = camera.proj * camera.view * p.extend(1.0);
+        let inside = clip.w > 0.0
+            && clip.x.abs() <= clip.w
+            && clip.y.abs() <= clip.w
+            && (0.0..=clip.w).contains(&clip.z);
+
+        inside.then(|| clip.truncate() / clip.w)
+    }
+
+    #[test]
+    fn sees_what_it_looks_at() {
+        let target = Vec3A::splat(16.0);
+        let camera = looking_at(vec3a(-20.0, 48.0, -20.0), target);
+
+        let center = ndc(&camera, target).unwrap();
+        assert!(center.x.abs() < 1e-4 && center.y.abs() < 1e-4);
+        assert_eq!(ndc(&camera, camera.pos - camera.front * 10.0), None);
+    }
+
+    #[test]
+    fn default_camera_sees_the_origin() {
+        assert!(ndc(&Camera::new(), Vec3A::ZERO).is_some());
+    }
+
+    #[test]
+    fn up_is_up_on_screen() {
+        let camera = looking_at(Vec3A::ZERO, Vec3A::Z);
+
+        // vulkan's y axis points down the screen
+        assert!(ndc(&camera, vec3a(0.0, 1.0, 5.0)).unwrap().y < 0.0);
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use vulkano::format::Format;
+
+// (bgra, srgb) for the 8 bit formats we can turn into a PNG
+fn layout(format: Format) -> Option<(bool, bool)> {
+    match format {
+        Format::R8G8B8A8_UNORM => Some((false, false)),
+        Format::R8G8B8A8_SRGB => Some((false, true)),
+        Format::B8G8R8A8_UNORM => Some((true, false)),
+        Format::B8G8R8A8_SRGB => Some((true, true)),
+        _ => None,
+    }
+}
+
+// whether to_rgba8 can convert frames of `format`, checked before anything is copied out
+pub fn supported(format: Format) -> bool {
+    layout(format).is_some()
+}
+
+// converts pixels read back from a color attachment to RGBA8, returns whether they are
+// sRGB encoded. None for formats we can't turn into a PNG
+pub fn to_rgba8(format: Format, mut pixels: Vec<u8>) -> Option<(Vec<u8>, bool)> {
+    let (bgra, srgb) = layout(format)?;
+
+    for px in pixels.chunks_exact_mut(4) {
+        if bgra {
+            px.swap(0, 2);
+        }
+        // swapchain alpha is whatever the clear color left there
+        px[3] = 255;
+    }
+
+    Some((pixels, srgb))
+}
+
+// `rgba` is written as is: for sRGB attachments the bytes already are sRGB, for UNORM ones
+// they are what the display was sent
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8], srgb: bool) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if srgb {
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+
+    Ok(())
+}
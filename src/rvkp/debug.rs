@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::instance::debug::{
+    DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+    DebugUtilsMessengerCallback, DebugUtilsMessengerCreateInfo,
+};
+use vulkano::instance::{Instance, InstanceExtensions};
+use vulkano::{VulkanLibrary, VulkanObject};
+
+use super::error::RvkpError;
+
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+// comma separated DebugConfig flags, e.g. RVKP_VALIDATION=on,verbose
+pub const VALIDATION_ENV_VAR: &str = "RVKP_VALIDATION";
+
+// validation errors reported since startup, the messenger callback can't panic itself because
+// vulkano catches unwinds at the ffi boundary
+static VALIDATION_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DebugConfig {
+    // VK_LAYER_KHRONOS_validation, if it's installed
+    pub validation: bool,
+    // info and verbose messages too, not just warnings and errors
+    pub verbose: bool,
+    // check() panics once a validation error came in, for tests
+    pub panic_on_error: bool,
+}
+
+impl DebugConfig {
+    // "on", "verbose" and "panic", the latter two imply "on"
+    pub fn parse(s: &str) -> Self {
+        let mut config = Self::default();
+
+        for flag in s.split(',').map(str::trim) {
+            match flag {
+                "on" | "1" => config.validation = true,
+                "verbose" => config.verbose = true,
+                "panic" => config.panic_on_error = true,
+                "off" | "0" | "" => (),
+                _ => println!("ignoring unknown {VALIDATION_ENV_VAR} flag {flag}"),
+            }
+        }
+
+        config.validation |= config.verbose || config.panic_on_error;
+        config
+    }
+
+    // from RVKP_VALIDATION, everything off when it isn't set
+    pub fn from_env() -> Self {
+        std::env::var(VALIDATION_ENV_VAR).map_or(Self::default(), |v| Self::parse(&v))
+    }
+
+    // layers to enable, empty if validation is off or the layer isn't installed
+    pub(crate) fn layers(&self, library: &VulkanLibrary) -> Vec<String> {
+        if !self.validation {
+            return vec![];
+        }
+
+        let available = library
+            .layer_properties()
+            .is_ok_and(|mut layers| layers.any(|l| l.name() == VALIDATION_LAYER));
+
+        if !available {
+            println!("{VALIDATION_LAYER} isn't installed, running without validation");
+            return vec![];
+        }
+
+        vec![VALIDATION_LAYER.to_string()]
+    }
+
+    pub(crate) fn extensions(&self, library: &VulkanLibrary) -> InstanceExtensions {
+        InstanceExtensions {
+            ext_debug_utils: self.validation && library.supported_extensions().ext_debug_utils,
+            ..InstanceExtensions::empty()
+        }
+    }
+
+    // None if debug utils aren't enabled on the instance
+    pub(crate) fn messenger(
+        &self,
+        instance: &Arc<Instance>,
+    ) -> Result<Option<DebugUtilsMessenger>, RvkpError> {
+        if !instance.enabled_extensions().ext_debug_utils {
+            return Ok(None);
+        }
+
+        let mut message_severity = DebugUtilsMessageSeverity::ERROR | DebugUtilsMessageSeverity::WARNING;
+        if self.verbose {
+            message_severity |= DebugUtilsMessageSeverity::INFO | DebugUtilsMessageSeverity::VERBOSE;
+        }
+
+        // safe, the callback only prints and counts
+        let callback = unsafe { DebugUtilsMessengerCallback::new(log_message) };
+
+        DebugUtilsMessenger::new(
+            instance.clone(),
+            DebugUtilsMessengerCreateInfo {
+                message_severity,
+                message_type: DebugUtilsMessageType::GENERAL
+                    | DebugUtilsMessageType::VALIDATION
+                    | DebugUtilsMessageType::PERFORMANCE,
+                ..DebugUtilsMessengerCreateInfo::user_callback(callback)
+            },
+        )
+        .map(Some)
+        .map_err(RvkpError::Instance)
+    }
+
+    // panics if validation errors came in and panic_on_error is set
+    pub fn check(&self) {
+        let errors = VALIDATION_ERRORS.load(Ordering::Relaxed);
+
+        if self.panic_on_error && errors > 0 {
+            panic!("{errors} vulkan validation error(s), see the log above");
+        }
+    }
+}
+
+fn log_message(
+    severity: DebugUtilsMessageSeverity,
+    ty: DebugUtilsMessageType,
+    data: vulkano::instance::debug::DebugUtilsMessengerCallbackData<'_>,
+) {
+    let id = data.message_id_name.unwrap_or("");
+
+    if severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+        if ty.intersects(DebugUtilsMessageType::VALIDATION) {
+            VALIDATION_ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        eprintln!("[vulkan error] {id} {}", data.message);
+    } else if severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+        eprintln!("[vulkan warning] {id} {}", data.message);
+    } else if severity.intersects(DebugUtilsMessageSeverity::INFO) {
+        println!("[vulkan info] {id} {}", data.message);
+    } else {
+        println!("[vulkan verbose] {id} {}", data.message);
+    }
+}
+
+pub fn validation_errors() -> usize {
+    VALIDATION_ERRORS.load(Ordering::Relaxed)
+}
+
+// shows up in validation messages and graphics debuggers, does nothing without debug utils
+pub fn set_name<T: VulkanObject + DeviceOwned>(device: &Arc<Device>, object: &T, name: &str) {
+    if device.instance().enabled_extensions().ext_debug_utils {
+        // naming is best effort, failing to isn't worth reporting
+        let _ = device.set_debug_utils_object_name(object, Some(name));
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::device::Features;
+use vulkano::instance::{Instance, InstanceCreateFlags, InstanceCreateInfo};
+use vulkano::memory::MemoryHeapFlags;
+use vulkano::{Validated, Version, VulkanLibrary};
+
+use super::error::RvkpError;
+
+// overrides the device policy when set, same syntax as DevicePolicy::parse
+pub const GPU_ENV_VAR: &str = "RVKP_GPU";
+
+#[derive(Debug, Clone)]
+pub struct MemoryHeapInfo {
+    pub size: u64,
+    pub device_local: bool,
+}
+
+// what we know about a physical device before picking it
+#[derive(Debug, Clone)]
+pub struct GpuInfo {
+    // position in the instance's device list, what DevicePolicy::Index refers to
+    pub index: usize,
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    pub api_version: Version,
+    pub driver_name: Option<String>,
+    pub driver_info: Option<String>,
+    pub driver_version: u32,
+    pub memory_heaps: Vec<MemoryHeapInfo>,
+    pub features: Features,
+}
+
+impl GpuInfo {
+    pub fn new(index: usize, p: &PhysicalDevice) -> Self {
+        let properties = p.properties();
+
+        Self {
+            index,
+            name: properties.device_name.clone(),
+            device_type: properties.device_type,
+            api_version: p.api_version(),
+            driver_name: properties.driver_name.clone(),
+            driver_info: properties.driver_info.clone(),
+            driver_version: properties.driver_version,
+            memory_heaps: p
+                .memory_properties()
+                .memory_heaps
+                .iter()
+                .map(|heap| MemoryHeapInfo {
+                    size: heap.size,
+                    device_local: heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL),
+                })
+                .collect(),
+            features: *p.supported_features(),
+        }
+    }
+
+    // total size of the device local heaps, roughly the vram
+    pub fn device_memory(&self) -> u64 {
+        self.memory_heaps.iter().filter(|h| h.device_local).map(|h| h.size).sum()
+    }
+}
+
+// every physical device vulkan can see, including ones VkImpl wouldn't pick
+pub fn list_gpus() -> Result<Vec<GpuInfo>, RvkpError> {
+    let instance = Instance::new(
+        VulkanLibrary::new()?,
+        InstanceCreateInfo {
+            flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+            ..Default::default()
+        },
+    )
+    .map_err(RvkpError::Instance)?;
+
+    Ok(enumerate(&instance)?
+        .iter()
+        .enumerate()
+        .map(|(i, p)| GpuInfo::new(i, p))
+        .collect())
+}
+
+pub(crate) fn enumerate(instance: &Arc<Instance>) -> Result<Vec<Arc<PhysicalDevice>>, RvkpError> {
+    Ok(instance
+        .enumerate_physical_devices()
+        .map_err(|e| RvkpError::Device(Validated::Error(e)))?
+        .collect())
+}
+
+// which device VkImpl picks among the ones that can render (and present)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DevicePolicy {
+    #[default]
+    PreferDiscrete,
+    PreferIntegrated,
+    // a device whose name contains this, ignoring case. discrete ones win ties
+    Name(String),
+    Index(usize),
+    // only a cpu implementation like lavapipe or swiftshader, for reproducible tests
+    Cpu,
+}
+
+impl DevicePolicy {
+    // "discrete", "integrated", "cpu", "index:N" or "name:SUBSTRING"
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "discrete" => Some(DevicePolicy::PreferDiscrete),
+            "integrated" => Some(DevicePolicy::PreferIntegrated),
+            "cpu" => Some(DevicePolicy::Cpu),
+            _ => {
+                if let Some(i) = s.strip_prefix("index:") {
+                    i.parse().ok().map(DevicePolicy::Index)
+                } else {
+                    s.strip_prefix("name:").map(|n| DevicePolicy::Name(n.to_string()))
+                }
+            }
+        }
+    }
+
+    // RVKP_GPU if it's set and valid, otherwise `fallback`
+    pub fn from_env_or(fallback: Self) -> Self {
+        let Ok(value) = std::env::var(GPU_ENV_VAR) else {
+            return fallback;
+        };
+
+        Self::parse(&value).unwrap_or_else(|| {
+            println!("ignoring {GPU_ENV_VAR}={value}, expected discrete, integrated, cpu, index:N or name:..");
+            fallback
+        })
+    }
+
+    fn rank(&self, device_type: PhysicalDeviceType) -> u8 {
+        let order: &[PhysicalDeviceType] = match self {
+            DevicePolicy::PreferIntegrated => &[
+                PhysicalDeviceType::IntegratedGpu,
+                PhysicalDeviceType::DiscreteGpu,
+                PhysicalDeviceType::VirtualGpu,
+                PhysicalDeviceType::Cpu,
+            ],
+            _ => &[
+                PhysicalDeviceType::DiscreteGpu,
+                PhysicalDeviceType::IntegratedGpu,
+                PhysicalDeviceType::VirtualGpu,
+                PhysicalDeviceType::Cpu,
+            ],
+        };
+
+        order.iter().position(|&t| t == device_type).unwrap_or(order.len()) as u8
+    }
+
+    // `candidates` are (index in the device list, device, queue family) of the usable devices
+    pub(crate) fn select(
+        &self,
+        candidates: Vec<(usize, Arc<PhysicalDevice>, u32)>,
+    ) -> Option<(usize, Arc<PhysicalDevice>, u32)> {
+        let name_matches = |p: &PhysicalDevice, name: &str| {
+            p.properties().device_name.to_lowercase().contains(&name.to_lowercase())
+        };
+
+        candidates
+            .into_iter()
+            .filter(|(i, p, _)| match self {
+                DevicePolicy::Name(name) => name_matches(p, name),
+                DevicePolicy::Index(index) => i == index,
+                DevicePolicy::Cpu => p.properties().device_type == PhysicalDeviceType::Cpu,
+                _ => true,
+            })
+            .min_by_key(|(i, p, _)| (self.rank(p.properties().device_type), *i))
+    }
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fmt;
+
+use vulkano::buffer::AllocateBufferError;
+use vulkano::command_buffer::CommandBufferExecError;
+use vulkano::format::Format;
+use vulkano::image::AllocateImageError;
+use vulkano::{LoadingError, Validated, ValidationError, VulkanError};
+
+// what can go wrong while setting up the renderer, so the application can tell the user or
+// fall back instead of dying on an unwrap
+#[derive(Debug)]
+pub enum RvkpError {
+    // no vulkan loader installed, or it couldn't be opened
+    NoLoader(LoadingError),
+    Instance(Validated<VulkanError>),
+    // no device with a graphics queue (that can present to the window, if there is one)
+    NoSuitableDevice,
+    Device(Validated<VulkanError>),
+    Window(winit::error::OsError),
+    Surface(Validated<VulkanError>),
+    Swapchain(Validated<VulkanError>),
+    BufferAllocation(Validated<AllocateBufferError>),
+    ImageAllocation(Validated<AllocateImageError>),
+    ShaderLoad(Validated<VulkanError>),
+    // render pass, framebuffers or pipeline
+    Pipeline(Validated<VulkanError>),
+    // submitting or waiting on a staging copy
+    Upload(Validated<VulkanError>),
+    // recording, submitting or waiting on a frame's commands
+    Submit(Validated<VulkanError>),
+    Execute(CommandBufferExecError),
+    // frames in this format can't be captured
+    UnsupportedFormat(Format),
+    // the frame can't be captured right now, e.g. the window is minimized
+    CaptureUnavailable(&'static str),
+}
+
+impl fmt::Display for RvkpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RvkpError::NoLoader(e) => write!(f, "couldn't load vulkan, is a driver installed? ({e})"),
+            RvkpError::Instance(e) => write!(f, "failed to create vulkan instance: {e}"),
+            RvkpError::NoSuitableDevice => write!(f, "no suitable graphics device found"),
+            RvkpError::Device(e) => write!(f, "failed to create device: {e}"),
+            RvkpError::Window(e) => write!(f, "failed to create window: {e}"),
+            RvkpError::Surface(e) => write!(f, "failed to create surface: {e}"),
+            RvkpError::Swapchain(e) => write!(f, "failed to create swapchain: {e}"),
+            RvkpError::BufferAllocation(e) => write!(f, "failed to allocate buffer: {e}"),
+            RvkpError::ImageAllocation(e) => write!(f, "failed to allocate image: {e}"),
+            RvkpError::ShaderLoad(e) => write!(f, "failed to load shader: {e}"),
+            RvkpError::Pipeline(e) => write!(f, "failed to create pipeline: {e}"),
+            RvkpError::Upload(e) => write!(f, "failed to upload to the gpu: {e}"),
+            RvkpError::Submit(e) => write!(f, "failed to submit commands: {e}"),
+            RvkpError::Execute(e) => write!(f, "failed to execute commands: {e}"),
+            RvkpError::UnsupportedFormat(format) => write!(f, "can't capture {format:?} frames"),
+            RvkpError::CaptureUnavailable(why) => write!(f, "can't capture a frame: {why}"),
+        }
+    }
+}
+
+impl Error for RvkpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RvkpError::NoLoader(e) => Some(e),
+            RvkpError::Window(e) => Some(e),
+            RvkpError::BufferAllocation(e) => Some(e),
+            RvkpError::ImageAllocation(e) => Some(e),
+            RvkpError::Execute(e) => Some(e),
+            RvkpError::NoSuitableDevice
+            | RvkpError::UnsupportedFormat(_)
+            | RvkpError::CaptureUnavailable(_) => None,
+            RvkpError::Instance(e)
+            | RvkpError::Device(e)
+            | RvkpError::Surface(e)
+            | RvkpError::Swapchain(e)
+            | RvkpError::ShaderLoad(e)
+            | RvkpError::Pipeline(e)
+            | RvkpError::Upload(e)
+            | RvkpError::Submit(e) => Some(e),
+        }
+    }
+}
+
+// for setup checks vulkano doesn't do itself, reported the same way as its own validation errors
+pub fn invalid(context: &'static str, problem: &'static str) -> Validated<VulkanError> {
+    Validated::ValidationError(Box::new(ValidationError {
+        context: context.into(),
+        problem: problem.into(),
+        ..Default::default()
+    }))
+}
+
+impl From<LoadingError> for RvkpError {
+    fn from(e: LoadingError) -> Self {
+        RvkpError::NoLoader(e)
+    }
+}
+
+impl From<winit::error::OsError> for RvkpError {
+    fn from(e: winit::error::OsError) -> Self {
+        RvkpError::Window(e)
+    }
+}
+
+impl From<Validated<AllocateBufferError>> for RvkpError {
+    fn from(e: Validated<AllocateBufferError>) -> Self {
+        RvkpError::BufferAllocation(e)
+    }
+}
+
+impl From<Validated<AllocateImageError>> for RvkpError {
+    fn from(e: Validated<AllocateImageError>) -> Self {
+        RvkpError::ImageAllocation(e)
+    }
+}
@@ -1,101 +1,157 @@
 use std::sync::{Arc, Mutex};
 
-use vulkano::buffer::subbuffer::Subbuffer;
-use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
-use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use glam::Mat4;
 
-use crate::rvkp::init::Vk;
-use crate::rvkp::presenter::vert;
-use crate::rvkp::presenter::InstanceData;
+use vulkano::buffer::subbuffer::Subbuffer;
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::pipeline::layout::PipelineLayout;
 
+use super::error::RvkpError;
+use super::transform::Transform;
+use super::upload::UploadTicket;
 use super::vk_impl::VkImpl;
-use super::vk_renderer::RVertex3d;
+use super::vk_renderer::{InstanceData, RVertex3d};
 
 
 #[derive(Clone)]
 pub struct Mesh {
     pub vert_buf: Option<Subbuffer<[RVertex3d]>>,    
     pub ind_buf: Option<Subbuffer<[u32]>>,    
-    pub inst_buf: Option<Subbuffer<[crate::rvkp::presenter::InstanceData]>>,
-    // transform_mat: [[f32; 4];4],
+    pub inst_buf: Option<Subbuffer<[InstanceData]>>,
+    // moving a mesh only changes its push constants, the buffers stay as they are
+    pub transform: Transform,
+    // batch that fills the buffers, the mesh isn't drawn before it's done
+    upload: UploadTicket,
+}
+
+// the model matrix sits right after the camera's view_proj in the push constant block
+const MODEL_OFFSET: u32 = std::mem::size_of::<[[f32; 4]; 4]>() as u32;
+
+// meshes without instances are drawn as one instance with no offset
+fn single_instance() -> Vec<InstanceData> {
+    vec![InstanceData::default()]
 }
 
 impl Mesh {
     pub fn new(
         verts: Vec<RVertex3d>, 
         inds: Vec<u32>,
-        instcs: Vec<crate::rvkp::presenter::InstanceData>,
+        instcs: Vec<InstanceData>,
         vk: &VkImpl,
-    ) -> Self { 
-        let vert_buf = Some(vk.vertex_buffer(verts));
-        let ind_buf = Some(vk.index_buffer(inds));
-        //let inst_buf = Some(vk.instance_buffer(instcs));
-
-        Self {
-            vert_buf,
-            ind_buf,
-            inst_buf: None, // for now
-        }
+    ) -> Result<Self, RvkpError> { 
+        let (vert_buf, _) = vk.vertex_buffer(verts)?;
+        let (ind_buf, _) = vk.index_buffer(inds)?;
+        let instcs = if instcs.is_empty() { single_instance() } else { instcs };
+        // batches finish in order, so the last buffer's ticket covers the others too
+        let (inst_buf, upload) = vk.instance_buffer(instcs)?;
+
+        Ok(Self {
+            vert_buf: Some(vert_buf),
+            ind_buf: Some(ind_buf),
+            inst_buf: Some(inst_buf),
+            transform: Transform::default(),
+            upload,
+        })
     }
 
-    pub fn quad(vk: &VkImpl) -> Self {
-        let vert_buf = vk.vertex_buffer(
+    pub fn quad(vk: &VkImpl) -> Result<Self, RvkpError> {
+        let (vert_buf, _) = vk.vertex_buffer(
             vec![
                 RVertex3d::new(0.1, 0.1, 0.0), 
                 RVertex3d::new(0.1, -0.1, 0.0),
                 RVertex3d::new(-0.1, 0.1, 0.0),
                 RVertex3d::new(-0.1, -0.1, 0.0),
             ],
-        );
+        )?;
 
-        let ind_buf = vk.index_buffer(vec![0, 1, 2, 2, 1, 3]);
-        let inst_buf = None;
+        let (ind_buf, _) = vk.index_buffer(vec![0, 1, 2, 2, 1, 3])?;
+        let (inst_buf, upload) = vk.instance_buffer(single_instance())?;
 
-        Self {
+        Ok(Self {
             vert_buf: Some(vert_buf),
             ind_buf: Some(ind_buf),
-            inst_buf,
-        }
+            inst_buf: Some(inst_buf),
+            transform: Transform::default(),
+            upload,
+        })
     }
 
-    pub fn vertices(mut self, v: Vec<RVertex3d>, vk: &VkImpl) -> Self {
-        self.vert_buf = Some(vk.vertex_buffer(v));
+    pub fn vertices(mut self, v: Vec<RVertex3d>, vk: &VkImpl) -> Result<Self, RvkpError> {
+        let (vert_buf, upload) = vk.vertex_buffer(v)?;
+        self.vert_buf = Some(vert_buf);
+        // batches finish in order, so the newest ticket covers the older buffers too
+        self.upload = upload;
 
-        self
+        Ok(self)
     }
 
-    pub fn indices(mut self, i: Vec<u32>, vk: &VkImpl) -> Self {
-        self.ind_buf = Some(vk.index_buffer(i));
+    pub fn indices(mut self, i: Vec<u32>, vk: &VkImpl) -> Result<Self, RvkpError> {
+        let (ind_buf, upload) = vk.index_buffer(i)?;
+        self.ind_buf = Some(ind_buf);
+        self.upload = upload;
 
-        self
+        Ok(self)
     }
 
-    // TODO!
-    pub fn instances(mut self, i: Vec<crate::rvkp::presenter::InstanceData>, vk: &Vk) -> Self {
-        self.inst_buf = Some(vk.instance_buffer(i));
+    pub fn transform(mut self, t: Transform) -> Self {
+        self.transform = t;
 
         self
     }
 
-    pub fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer<StandardCommandBufferAllocator>>) {
+    pub fn instances(mut self, i: Vec<InstanceData>, vk: &VkImpl) -> Result<Self, RvkpError> {
+        let i = if i.is_empty() { single_instance() } else { i };
+        let (inst_buf, upload) = vk.instance_buffer(i)?;
+        self.inst_buf = Some(inst_buf);
+        self.upload = upload;
+
+        Ok(self)
+    }
+
+    pub fn ticket(&self) -> &UploadTicket {
+        &self.upload
+    }
+
+    // whether the buffers were copied to the gpu yet
+    pub fn is_uploaded(&self) -> bool {
+        self.upload.is_done()
+    }
+
+    // draws the mesh with its own transform as the model matrix
+    pub fn draw<L, A: CommandBufferAllocator>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        layout: &Arc<PipelineLayout>,
+    ) {
+        self.draw_with(builder, layout, self.transform.matrix());
+    }
+
+    // pushes `model` and draws the mesh, once per instance. does nothing while the upload is
+    // still in flight
+    pub fn draw_with<L, A: CommandBufferAllocator>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        layout: &Arc<PipelineLayout>,
+        model: Mat4,
+    ) {
+        if !self.is_uploaded() {
+            return;
+        }
+
         let vert_buf = self.vert_buf.clone().unwrap();
         let ind_buf = self.ind_buf.clone().unwrap();
-        if let Some(inst_buf) = self.inst_buf.clone() {
-            builder
-                .bind_vertex_buffers(0, (vert_buf.clone(), inst_buf.clone()))
-                .unwrap()
-                .bind_index_buffer(ind_buf.clone())
-                .unwrap()
-                .draw_indexed(ind_buf.len() as u32, inst_buf.len() as u32, 0, 0, 0)
-                .unwrap();
-        } else {
-            builder
-                .bind_vertex_buffers(0, vert_buf.clone())
-                .unwrap()
-                .bind_index_buffer(ind_buf.clone())
-                .unwrap()
-                .draw_indexed(ind_buf.len() as u32, 0 as u32, 0, 0, 0)
-                .unwrap();
-        }
+        // always set, meshes without instances get a single one
+        let inst_buf = self.inst_buf.clone().unwrap();
+
+        builder
+            .push_constants(layout.clone(), MODEL_OFFSET, model.to_cols_array_2d())
+            .unwrap()
+            .bind_vertex_buffers(0, (vert_buf, inst_buf.clone()))
+            .unwrap()
+            .bind_index_buffer(ind_buf.clone())
+            .unwrap()
+            .draw_indexed(ind_buf.len() as u32, inst_buf.len() as u32, 0, 0, 0)
+            .unwrap();
     }
 }
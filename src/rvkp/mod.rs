@@ -1,9 +1,14 @@
-pub mod init;
 pub mod mesh;
-pub mod presenter;
 pub mod buffer;
 pub mod view_utils;
 pub mod shader;
 pub mod camera;
 pub mod vk_impl;
 pub mod vk_renderer;
+pub mod capture;
+pub mod error;
+pub mod device;
+pub mod debug;
+pub mod upload;
+pub mod transform;
+pub mod scene;
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use glam::Mat4;
+
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::pipeline::layout::PipelineLayout;
+
+use super::mesh::Mesh;
+use super::transform::Transform;
+
+// index into Scene::nodes, only valid for the scene that handed it out and until the node is
+// removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    pub mesh: Option<Mesh>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    // parent world * local, refreshed by Scene::update
+    world: Mat4,
+}
+
+impl Node {
+    fn new(name: &str, parent: Option<NodeId>) -> Self {
+        Self {
+            name: name.to_string(),
+            transform: Transform::default(),
+            mesh: None,
+            parent,
+            children: vec![],
+            world: Mat4::IDENTITY,
+        }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    // as of the last Scene::update
+    pub fn world(&self) -> Mat4 {
+        self.world
+    }
+}
+
+// tree of nodes hanging off a single root. a node's transform is relative to its parent, the
+// mesh attached to it is drawn with the node's world transform times the mesh's own
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    root: NodeId,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Some(Node::new("root", None))],
+            free: vec![],
+            root: NodeId(0),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn add(&mut self, parent: NodeId, name: &str) -> NodeId {
+        assert!(self.contains(parent), "parent {parent:?} is not in the scene");
+
+        let node = Some(Node::new(name, Some(parent)));
+        let id = match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                NodeId(i)
+            }
+            None => {
+                self.nodes.push(node);
+                NodeId(self.nodes.len() - 1)
+            }
+        };
+
+        self.node_mut(parent).children.push(id);
+        id
+    }
+
+    pub fn add_mesh(&mut self, parent: NodeId, name: &str, mesh: Mesh) -> NodeId {
+        let id = self.add(parent, name);
+        self.node_mut(id).mesh = Some(mesh);
+
+        id
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        matches!(self.nodes.get(id.0), Some(Some(_)))
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0).and_then(|n| n.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0).and_then(|n| n.as_mut())
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.get(id).unwrap()
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.get_mut(id).unwrap()
+    }
+
+    // first node with that name, depth first from the root
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|&id| self.node(id).name == name)
+    }
+
+    // depth first, parents before their children
+    pub fn iter(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack = vec![self.root];
+
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.node(id).children.iter().rev());
+
+            Some(id)
+        })
+    }
+
+    // removes the node and everything below it, returns the node's mesh. the root can't be
+    // removed, use clear for that
+    pub fn remove(&mut self, id: NodeId) -> Option<Mesh> {
+        if id == self.root || !self.contains(id) {
+            return None;
+        }
+
+        if let Some(parent) = self.node(id).parent {
+            self.node_mut(parent).children.retain(|&c| c != id);
+        }
+
+        let mut stack = vec![id];
+        let mut mesh = None;
+        while let Some(i) = stack.pop() {
+            let node = self.nodes[i.0].take().unwrap();
+            stack.extend(node.children);
+            self.free.push(i.0);
+
+            if i == id {
+                mesh = node.mesh;
+            }
+        }
+
+        mesh
+    }
+
+    // moves a node (and its subtree) under another parent, keeping its local transform
+    pub fn set_parent(&mut self, id: NodeId, parent: NodeId) {
+        assert!(id != self.root, "the root can't be reparented");
+        assert!(self.contains(id) && self.contains(parent));
+
+        let mut ancestor = Some(parent);
+        while let Some(a) = ancestor {
+            assert!(a != id, "{parent:?} is below {id:?}, reparenting would make a cycle");
+            ancestor = self.node(a).parent;
+        }
+
+        let old = self.node(id).parent.unwrap();
+        self.node_mut(old).children.retain(|&c| c != id);
+        self.node_mut(parent).children.push(id);
+        self.node_mut(id).parent = Some(parent);
+    }
+
+    // drops every node except the root
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    // nothing to draw. nodes without a mesh, like the root or the renderer's chunk root, don't
+    // count
+    pub fn is_empty(&self) -> bool {
+        self.nodes.iter().flatten().all(|node| node.mesh.is_none())
+    }
+
+    // propagates the local transforms down the tree, once per frame before drawing
+    pub fn update(&mut self) {
+        let mut stack = vec![(self.root, Mat4::IDENTITY)];
+
+        while let Some((id, parent_world)) = stack.pop() {
+            let node = self.node_mut(id);
+            node.world = parent_world * node.transform.matrix();
+
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&c| (c, world)));
+        }
+    }
+
+    // draws every attached mesh, world transforms come from the last update
+    pub fn draw<L, A: CommandBufferAllocator>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+        layout: &Arc<PipelineLayout>,
+    ) {
+        for id in self.iter() {
+            let node = self.node(id);
+
+            if let Some(mesh) = &node.mesh {
+                mesh.draw_with(builder, layout, node.world * mesh.transform.matrix());
+            }
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, vec3a, Quat};
+
+    use super::*;
+
+    #[test]
+    fn children_inherit_the_parent_transform() {
+        let mut scene = Scene::new();
+        let parent = scene.add(scene.root(), "parent");
+        let child = scene.add(parent, "child");
+        let grandchild = scene.add(child, "grandchild");
+
+        scene.node_mut(parent).transform = Transform::new()
+            .position(vec3a(10.0, 0.0, 0.0))
+            .rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        scene.node_mut(child).transform = Transform::new().position(vec3a(0.0, 0.0, 1.0));
+        scene.node_mut(grandchild).transform = Transform::new().scale(vec3a(2.0, 2.0, 2.0));
+        scene.update();
+
+        // the child's +z offset gets turned into +x by the parent's rotation
+        let origin = scene.node(child).world().transform_point3(vec3(0.0, 0.0, 0.0));
+        assert!(origin.abs_diff_eq(vec3(11.0, 0.0, 0.0), 1e-5), "{origin}");
+
+        let expected = scene.node(child).world() * Mat4::from_scale(vec3(2.0, 2.0, 2.0));
+        assert!(scene.node(grandchild).world().abs_diff_eq(expected, 1e-5));
+
+        // moving the parent moves everything below it with the next update
+        scene.node_mut(parent).transform.translate(vec3a(0.0, 5.0, 0.0));
+        scene.update();
+
+        let origin = scene.node(grandchild).world().transform_point3(vec3(0.0, 0.0, 0.0));
+        assert!(origin.abs_diff_eq(vec3(11.0, 5.0, 0.0), 1e-5), "{origin}");
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut scene = Scene::new();
+        let a = scene.add(scene.root(), "a");
+        let b = scene.add(a, "b");
+        let c = scene.add(scene.root(), "c");
+
+        // takes b with it
+        scene.remove(a);
+        assert!(!scene.contains(a) && !scene.contains(b));
+        assert!(scene.contains(c));
+        assert_eq!(scene.node(scene.root()).children(), &[c]);
+
+        let d = scene.add(c, "d");
+        let e = scene.add(c, "e");
+        assert!([a, b].contains(&d) && [a, b].contains(&e) && d != e);
+        assert_eq!(scene.nodes.len(), 4);
+
+        // the new nodes don't inherit anything from the removed ones
+        assert_eq!(scene.node(d).parent(), Some(c));
+        assert!(scene.node(d).children().is_empty());
+        assert_eq!(scene.find("b"), None);
+        assert_eq!(scene.find("e"), Some(e));
+    }
+
+    #[test]
+    fn nodes_without_meshes_are_empty() {
+        let mut scene = Scene::new();
+        assert!(scene.is_empty());
+
+        let chunks = scene.add(scene.root(), "chunks");
+        scene.add(chunks, "group");
+        assert!(scene.is_empty());
+    }
+}
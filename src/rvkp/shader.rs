@@ -8,10 +8,17 @@ pub mod vs {
 #version 460
 
 layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 color;
 
+// per instance data
+layout(location = 2) in vec3 ofs;
+
+layout(location = 1) out vec3 s_color;
+
+// kept at 128 bytes, the minimum every device supports
 layout(push_constant) uniform PushConstantCameraData {
-    mat4 proj;
-    mat4 view;
+    mat4 view_proj;
+    mat4 model;
 };
 
 void main() {
@@ -21,7 +28,8 @@ void main() {
   // pos = vec3(outUV * 2.0 - 1.0, 0.0);
   //
 
-  gl_Position = proj * view * vec4(position, 1.0);
+  gl_Position = view_proj * model * vec4(position + ofs, 1.0);
+  s_color = color;
 }
         "#,
     }
@@ -0,0 +1,60 @@
+use glam::f32::*;
+
+// position/rotation/scale of a mesh, turned into its model matrix when it gets drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: Vec3A,
+    pub rotation: Quat,
+    pub scale: Vec3A,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: Vec3A::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3A::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn position(mut self, position: Vec3A) -> Self {
+        self.position = position;
+
+        self
+    }
+
+    pub fn rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+
+        self
+    }
+
+    pub fn scale(mut self, scale: Vec3A) -> Self {
+        self.scale = scale;
+
+        self
+    }
+
+    pub fn translate(&mut self, by: Vec3A) {
+        self.position += by;
+    }
+
+    pub fn rotate(&mut self, by: Quat) {
+        self.rotation = (by * self.rotation).normalize();
+    }
+
+    // scale first, then rotate, then translate
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            self.scale.into(),
+            self.rotation,
+            self.position.into(),
+        )
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage, CopyBufferInfo};
+use vulkano::device::{Device, Queue};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+use vulkano::sync::future::{FenceSignalFuture, NowFuture};
+use vulkano::sync::{self, GpuFuture, Sharing};
+
+use super::debug;
+use super::error::RvkpError;
+
+type UploadFence = Arc<FenceSignalFuture<CommandBufferExecFuture<NowFuture>>>;
+
+// batches that were submitted but not cleaned up yet, oldest first. every batch goes to the
+// uploader's one transfer queue, and a fence from vkQueueSubmit also waits for everything
+// submitted to that same queue before it, so batches finish in order. that doesn't hold across
+// queues, nothing else may submit batches here
+struct InFlight {
+    submitted: u64,
+    finished: u64,
+    fences: VecDeque<(u64, UploadFence)>,
+}
+
+impl InFlight {
+    // cleans up the batches whose fence signaled, which also releases their buffers for use
+    // on other queues. a batch that failed (e.g. the device was lost) stays unfinished
+    fn poll(&mut self) -> Result<(), RvkpError> {
+        while let Some((batch, fence)) = self.fences.front() {
+            let batch = *batch;
+            if !fence.is_signaled().map_err(|e| RvkpError::Upload(e.into()))? {
+                break;
+            }
+            fence.wait(None).map_err(RvkpError::Upload)?;
+
+            self.finished = batch;
+            self.fences.pop_front();
+        }
+
+        Ok(())
+    }
+}
+
+// tells when the copies of one batch landed in their device local buffers
+#[derive(Clone)]
+pub struct UploadTicket {
+    batch: u64,
+    in_flight: Arc<Mutex<InFlight>>,
+}
+
+impl UploadTicket {
+    // false until the batch was flushed and the gpu finished copying it. errors count as not
+    // done, the next Uploader::flush reports them
+    pub fn is_done(&self) -> bool {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let _ = in_flight.poll();
+
+        self.batch <= in_flight.finished
+    }
+}
+
+// stages data in host visible buffers and copies it into device local ones on the transfer
+// queue. copies are batched until flush(), which submits them all in one command buffer
+pub struct Uploader {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory: Arc<StandardMemoryAllocator>,
+    command_buffer: Arc<StandardCommandBufferAllocator>,
+    // graphics and transfer family when they differ, so buffers don't need ownership transfers
+    queue_family_indices: Vec<u32>,
+    pending: Mutex<Vec<CopyBufferInfo>>,
+    in_flight: Arc<Mutex<InFlight>>,
+}
+
+impl Uploader {
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        graphics_family: u32,
+        memory: Arc<StandardMemoryAllocator>,
+        command_buffer: Arc<StandardCommandBufferAllocator>,
+    ) -> Self {
+        let mut queue_family_indices = vec![graphics_family];
+        if queue.queue_family_index() != graphics_family {
+            queue_family_indices.push(queue.queue_family_index());
+        }
+
+        Self {
+            device,
+            queue,
+            memory,
+            command_buffer,
+            queue_family_indices,
+            pending: Mutex::new(vec![]),
+            in_flight: Arc::new(Mutex::new(InFlight {
+                submitted: 0,
+                finished: 0,
+                fences: VecDeque::new(),
+            })),
+        }
+    }
+
+    // device local buffer that gets `data` with the next flush. it can't be used by the
+    // renderer before the returned ticket is done
+    pub fn buffer<T: BufferContents>(
+        &self,
+        data: Vec<T>,
+        usage: BufferUsage,
+        name: &str,
+    ) -> Result<(Subbuffer<[T]>, UploadTicket), RvkpError> {
+        let len = data.len() as u64;
+
+        let staging = Buffer::from_iter(
+            self.memory.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            data,
+        )?;
+        debug::set_name(&self.device, &**staging.buffer(), &format!("{name} (staging)"));
+
+        let sharing = if self.queue_family_indices.len() > 1 {
+            Sharing::Concurrent(self.queue_family_indices.clone().into())
+        } else {
+            Sharing::Exclusive
+        };
+
+        let buffer = Buffer::new_slice::<T>(
+            self.memory.clone(),
+            BufferCreateInfo {
+                usage: usage | BufferUsage::TRANSFER_DST,
+                sharing,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            len,
+        )?;
+        debug::set_name(&self.device, &**buffer.buffer(), name);
+
+        // the batch is read under the pending lock, which flush() holds until it counted the
+        // batch it took, so the copy always lands in the batch of its ticket
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(CopyBufferInfo::buffers(staging, buffer.clone()));
+        let ticket = UploadTicket {
+            batch: self.in_flight.lock().unwrap().submitted + 1,
+            in_flight: self.in_flight.clone(),
+        };
+
+        Ok((buffer, ticket))
+    }
+
+    // submits every copy queued since the last flush. the staging buffers stay alive in the
+    // command buffer until the batch is cleaned up. if that fails the copies stay queued, so
+    // their tickets still refer to the next batch that does go out
+    pub fn flush(&self) -> Result<UploadTicket, RvkpError> {
+        // same lock order as buffer(), pending first
+        let mut pending = self.pending.lock().unwrap();
+        let mut in_flight = self.in_flight.lock().unwrap();
+        in_flight.poll()?;
+
+        if pending.is_empty() {
+            return Ok(UploadTicket {
+                batch: in_flight.submitted,
+                in_flight: self.in_flight.clone(),
+            });
+        }
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .map_err(RvkpError::Upload)?;
+        for copy in pending.iter() {
+            builder.copy_buffer(copy.clone()).map_err(|e| RvkpError::Upload(e.into()))?;
+        }
+        let command_buffer = builder.build().map_err(RvkpError::Upload)?;
+
+        let fence = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .map_err(RvkpError::Execute)?
+            .then_signal_fence_and_flush()
+            .map_err(RvkpError::Upload)?;
+
+        pending.clear();
+        in_flight.submitted += 1;
+        let batch = in_flight.submitted;
+        in_flight.fences.push_back((batch, Arc::new(fence)));
+
+        Ok(UploadTicket {
+            batch,
+            in_flight: self.in_flight.clone(),
+        })
+    }
+
+    // blocks until the ticket's batch is done, flushing first if it wasn't submitted yet
+    pub fn wait(&self, ticket: &UploadTicket) -> Result<(), RvkpError> {
+        if ticket.batch > self.in_flight.lock().unwrap().submitted {
+            self.flush()?;
+        }
+
+        let mut in_flight = self.in_flight.lock().unwrap();
+        while let Some((batch, fence)) = in_flight.fences.front() {
+            let batch = *batch;
+            if batch > ticket.batch {
+                break;
+            }
+            fence.wait(None).map_err(RvkpError::Upload)?;
+
+            in_flight.finished = batch;
+            in_flight.fences.pop_front();
+        }
+
+        Ok(())
+    }
+}
@@ -1,21 +1,73 @@
-use crate::rvkp::{presenter::VkView, mesh::Mesh};
+use crate::rvkp::{mesh::Mesh, scene::NodeId, vk_renderer::Renderer};
+use crate::world::ChunkCoord;
 
-use vulkano::buffer::Subbuffer;
-
-impl VkView {
+impl Renderer {
+    // adds the mesh as a new node under the scene root
     pub fn push_mesh(
         &mut self,
         mesh: Mesh,
+    ) -> NodeId {
+        let root = self.scene.root();
+        self.scene.add_mesh(root, "mesh", mesh)
+    }
+
+    // replaces the mesh of a chunk, if it had one. the old mesh stays until the new one is
+    // uploaded so remeshing doesn't make the chunk blink
+    pub fn set_chunk_mesh(
+        &mut self,
+        coord: ChunkCoord,
+        mesh: Mesh,
     ) {
-        self.meshes.push(mesh);
+        match self.chunk_nodes.get(&coord) {
+            Some(&node) if !mesh.is_uploaded() && self.scene.node(node).mesh.is_some() => {
+                self.pending_chunks.insert(coord, mesh);
+            }
+            Some(&node) => {
+                self.pending_chunks.remove(&coord);
+                self.scene.node_mut(node).mesh = Some(mesh);
+            }
+            None => {
+                let name = format!("chunk {} {} {}", coord[0], coord[1], coord[2]);
+                let node = self.scene.add_mesh(self.chunk_root, &name, mesh);
+                self.chunk_nodes.insert(coord, node);
+            }
+        }
+    }
+
+    pub fn remove_chunk_mesh(
+        &mut self,
+        coord: ChunkCoord,
+    ) -> Option<Mesh> {
+        self.pending_chunks.remove(&coord);
+        let node = self.chunk_nodes.remove(&coord)?;
+        self.scene.remove(node)
+    }
+
+    // swaps in the chunk meshes set_chunk_mesh held back, once their upload is done
+    pub fn swap_uploaded_chunks(
+        &mut self,
+    ) {
+        let uploaded: Vec<ChunkCoord> = self
+            .pending_chunks
+            .iter()
+            .filter(|(_, mesh)| mesh.is_uploaded())
+            .map(|(&coord, _)| coord)
+            .collect();
+
+        for coord in uploaded {
+            let mesh = self.pending_chunks.remove(&coord).unwrap();
+            let node = self.chunk_nodes[&coord];
+            self.scene.node_mut(node).mesh = Some(mesh);
+        }
     }
 
-    pub fn set_mesh(
+    // empty scene, chunk meshes included
+    pub fn clear_scene(
         &mut self,
-        idx: usize,
-        vert: vulkano::buffer::Subbuffer<[crate::rvkp::presenter::FVertex3d]>,
-        indx: vulkano::buffer::Subbuffer<[u32]>,
     ) {
-        todo!();
+        self.scene.clear();
+        self.chunk_nodes.clear();
+        self.pending_chunks.clear();
+        self.chunk_root = self.scene.add(self.scene.root(), "chunks");
     }
 }
@@ -6,11 +6,11 @@ use vulkano::{
         physical::PhysicalDeviceType, Device, DeviceCreateInfo, DeviceExtensions, DeviceOwned, Queue, QueueCreateInfo, QueueFlags
     }, format::Format, image::{view::ImageView, Image, ImageCreateInfo, ImageType, ImageUsage}, instance::{Instance, InstanceCreateFlags, InstanceCreateInfo}, memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator}, pipeline::{
         graphics::{
-            color_blend::{ColorBlendAttachmentState, ColorBlendState}, depth_stencil::{DepthState, DepthStencilState}, input_assembly::InputAssemblyState, multisample::MultisampleState, rasterization::RasterizationState, vertex_input::{Vertex, VertexDefinition}, viewport::{Viewport, ViewportState}, GraphicsPipelineCreateInfo
+            color_blend::{ColorBlendAttachmentState, ColorBlendState}, depth_stencil::{DepthState, DepthStencilState}, input_assembly::InputAssemblyState, multisample::MultisampleState, rasterization::RasterizationState, vertex_input::{Vertex, VertexDefinition}, viewport::{Scissor, Viewport, ViewportState}, GraphicsPipelineCreateInfo
         }, layout::PipelineDescriptorSetLayoutCreateInfo, DynamicState, GraphicsPipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo
     }, render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass}, shader::EntryPoint, swapchain::{
-        self, acquire_next_image, PresentFuture, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo, SwapchainPresentInfo
-    }, sync::{self, future::{FenceSignalFuture, JoinFuture}, GpuFuture}, Validated, VulkanError, VulkanLibrary
+        self, acquire_next_image, PresentFuture, PresentMode, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo, SwapchainPresentInfo
+    }, sync::{self, future::{FenceSignalFuture, JoinFuture}, GpuFuture}, instance::debug::DebugUtilsMessenger, Validated, VulkanError, VulkanLibrary
 };
 use winit::{
     event::{Event, WindowEvent},
@@ -19,8 +19,13 @@ use winit::{
 };
 
 use crate::rvkp::vk_renderer::RVertex3d;
+use crate::rvkp::vk_renderer::InstanceData;
 
-use super::{init::Vk, vk_renderer::Renderer};
+use super::debug::{self, DebugConfig};
+use super::device::{self, DevicePolicy, GpuInfo};
+use super::error::{self, RvkpError};
+use super::upload::Uploader;
+use super::vk_renderer::Renderer;
 
 #[derive(Debug)]
 pub struct Allocators {
@@ -45,100 +50,243 @@ impl Allocators {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Vsync {
+    // capped to the display, never tears
+    #[default]
+    On,
+    // as fast as possible, may tear
+    Off,
+    // vsync while keeping up, tears instead of stalling when a frame is late
+    Adaptive,
+}
+
+impl Vsync {
+    // preferred present modes, best first. FIFO is always supported so every chain ends there
+    fn chain(self) -> &'static [PresentMode] {
+        match self {
+            Vsync::On => &[PresentMode::Fifo],
+            Vsync::Off => &[PresentMode::Immediate, PresentMode::Mailbox, PresentMode::Fifo],
+            Vsync::Adaptive => &[PresentMode::FifoRelaxed, PresentMode::Fifo],
+        }
+    }
+
+    pub fn present_mode(self, supported: &[PresentMode]) -> PresentMode {
+        self.chain()
+            .iter()
+            .copied()
+            .find(|m| supported.contains(m))
+            .unwrap_or(PresentMode::Fifo)
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Vsync::On => Vsync::Off,
+            Vsync::Off => Vsync::Adaptive,
+            Vsync::Adaptive => Vsync::On,
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "on" => Some(Vsync::On),
+            "off" => Some(Vsync::Off),
+            "adaptive" => Some(Vsync::Adaptive),
+            _ => None,
+        }
+    }
+}
+
+type FrameFence = Arc<FenceSignalFuture<PresentFuture<CommandBufferExecFuture<JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>>>>>;
+
+// device, graphics queue, transfer queue (the graphics one again if there's no separate
+// family) and what was picked
+type DeviceQueues = (Arc<Device>, Arc<Queue>, Arc<Queue>, GpuInfo);
+
+// one frame can be in flight per swapchain image. every image has the fence of the last frame
+// rendered to it, which is waited on before the image (and whatever that frame used) is reused
 pub struct VkPresenter {
     pub recreate_swapchain: bool,
-    
 
-    pub previous_frame_end: Option<Box<(dyn GpuFuture + 'static)>>,
     pub frames_in_flight: usize,
+    pub fences: Vec<Option<FrameFence>>,
+    pub previous_fence_i: u32,
 }
 
 impl VkPresenter {
+    // the renderer is created before VkImpl::ignition, so there may be no images yet. the fences
+    // are sized once the first frame is acquired
     pub fn new(vk: &VkImpl) -> Self {
-        Self {
+        let mut presenter = Self {
             recreate_swapchain: false,
-            frames_in_flight: vk.images.len(),
-            previous_frame_end: Some(sync::now(vk.device.clone()).boxed()),
-        }
+            frames_in_flight: 0,
+            fences: vec![],
+            previous_fence_i: 0,
+        };
+        presenter.fit_images(vk.images.len());
+
+        presenter
     }
 
-    pub fn if_recreate_swapchain(&mut self, vk: &mut VkImpl) {
-        let image_extent: [u32; 2] = vk.window.inner_size().into();
-        if image_extent.contains(&0) {
+    // one fence slot per swapchain image. the old fences are dropped, so every frame using them
+    // has to be waited on before the image count changes
+    fn fit_images(&mut self, image_count: usize) {
+        if self.fences.len() == image_count {
             return;
         }
 
-        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+        self.frames_in_flight = image_count;
+        self.fences = vec![None; image_count];
+        self.previous_fence_i = 0;
+    }
+
+    // takes effect with the next frame, the swapchain is recreated with the new present mode
+    pub fn set_vsync(&mut self, vk: &mut VkImpl, vsync: Vsync) {
+        if vk.vsync != vsync {
+            vk.vsync = vsync;
+            self.recreate_swapchain = true;
+        }
+    }
+
+    // blocks until every frame in flight is done
+    pub fn wait_idle(&mut self) -> Result<(), RvkpError> {
+        for fence in self.fences.iter_mut() {
+            if let Some(fence) = fence.take() {
+                fence.wait(None).map_err(RvkpError::Submit)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // recreates the swapchain and everything sized after it if it went out of date or the window
+    // was resized. false while the window is minimized, nothing can be rendered until it's back
+    pub fn if_recreate_swapchain(&mut self, vk: &mut VkImpl) -> Result<bool, RvkpError> {
+        // nothing to recreate when rendering offscreen
+        let Some(window) = vk.window.clone() else {
+            return Ok(true);
+        };
+
+        let image_extent: [u32; 2] = window.inner_size().into();
+        if image_extent.contains(&0) {
+            return Ok(false);
+        }
 
         if self.recreate_swapchain {
+            // the old images and framebuffers may still be in use
+            self.wait_idle()?;
+
             let (new_swapchain, new_images) = vk.swapchain.clone().unwrap()
             .recreate(SwapchainCreateInfo {
                 image_extent,
+                present_mode: vk.present_mode()?,
                 ..vk.swapchain.clone().unwrap().create_info()
             })
-            .expect("failed to recreate swapchain");
+            .map_err(RvkpError::Swapchain)?;
     
             vk.swapchain = Some(new_swapchain);
-            let (new_pipeline, new_framebuffers) = VkImpl::window_size_dependent_setup(
-                vk,
-            );
-            vk.pipeline = Some(new_pipeline);
-            vk.framebuffers = new_framebuffers;
+            vk.images = new_images;
+            vk.extent = image_extent;
+            vk.framebuffers = VkImpl::window_size_dependent_setup(vk)?;
+
+            // the image count can change with the swapchain
+            self.fit_images(vk.images.len());
 
             self.recreate_swapchain = false;
         }
+
+        Ok(true)
     }
 
-    pub fn present(&mut self, vk: &VkImpl, command_buffer: Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>>) {
-        // TODO: make this function more based off of TEAPOT.rs in vulkano github
+    // next swapchain image to render to, None if the swapchain has to be recreated first. once
+    // this returns, the previous frame rendered to that image has finished, so its framebuffer
+    // can be recorded into
+    pub fn acquire(&mut self, vk: &VkImpl) -> Result<Option<(u32, SwapchainAcquireFuture)>, RvkpError> {
+        // no frame was rendered yet if the swapchain came after the presenter
+        self.fit_images(vk.images.len());
+
         let (image_index, suboptimal, acquire_future) =
-            match acquire_next_image(vk.swapchain.clone().unwrap(), None).map_err(Validated::unwrap) {
+            match acquire_next_image(vk.swapchain.clone().unwrap(), None) {
                 Ok(r) => r,
-                Err(VulkanError::OutOfDate) => {
+                Err(Validated::Error(VulkanError::OutOfDate)) => {
                     self.recreate_swapchain = true;
-                    return;
+                    return Ok(None);
                 }
-                Err(e) => panic!("failed to acquire next image: {e}"),
+                Err(e) => return Err(RvkpError::Swapchain(e)),
             };
 
         if suboptimal {
             self.recreate_swapchain = true;
         }
 
-        let future = self.previous_frame_end
-            .take()
-            .unwrap()
+        if let Some(fence) = &self.fences[image_index as usize] {
+            fence.wait(None).map_err(RvkpError::Submit)?;
+        }
+
+        Ok(Some((image_index, acquire_future)))
+    }
+
+    // executes `command_buffer` once the image is acquired and presents it. an out of date
+    // swapchain isn't an error, it gets recreated before the next frame
+    pub fn submit(
+        &mut self,
+        vk: &VkImpl,
+        image_index: u32,
+        acquire_future: SwapchainAcquireFuture,
+        command_buffer: Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>>,
+    ) -> Result<(), RvkpError> {
+        // frames are submitted in order, each one after the previous frame's work
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            Some(fence) => fence.boxed(),
+            None => {
+                let mut now = sync::now(vk.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+        };
+
+        let future = previous_future
             .join(acquire_future)
             .then_execute(vk.queue.clone(), command_buffer)
-            .unwrap()
+            .map_err(RvkpError::Execute)?
             .then_swapchain_present(
                 vk.queue.clone(),
                 SwapchainPresentInfo::swapchain_image_index(vk.swapchain.clone().unwrap(), image_index),
             )
             .then_signal_fence_and_flush();
 
-        match future.map_err(Validated::unwrap) {
-            Ok(future) => {
-                self.previous_frame_end = Some(future.boxed());
-            }
-            Err(VulkanError::OutOfDate) => {
-                self.recreate_swapchain = true;
-                self.previous_frame_end = Some(sync::now(vk.device.clone()).boxed());
-            }
-            Err(e) => {
-                println!("failed to flush future: {e}");
-                self.previous_frame_end = Some(sync::now(vk.device.clone()).boxed());
-            }
+        // the image's old fence was waited on in acquire, a failed frame leaves nothing to wait for
+        self.fences[image_index as usize] = None;
+        self.previous_fence_i = image_index;
+
+        match future {
+            Ok(future) => self.fences[image_index as usize] = Some(Arc::new(future)),
+            Err(Validated::Error(VulkanError::OutOfDate)) => self.recreate_swapchain = true,
+            Err(e) => return Err(RvkpError::Submit(e)),
         }
 
+        Ok(())
     }
 }
 
 pub struct VkImpl {
-    pub window: Arc<winit::window::Window>,
-    pub surface: Arc<Surface>,
+    // both None in headless mode
+    pub window: Option<Arc<winit::window::Window>>,
+    pub surface: Option<Arc<Surface>>,
     pub device: Arc<vulkano::device::Device>,
     pub queue: Arc<Queue>,
+    // a dedicated transfer queue if the device has one, otherwise the graphics queue again
+    pub transfer_queue: Arc<Queue>,
+    // the physical device that was picked
+    pub gpu: GpuInfo,
+    // size of the images being rendered to
+    pub extent: [u32; 2],
+    // set before ignition or through VkPresenter::set_vsync
+    pub vsync: Vsync,
+    pub debug: DebugConfig,
+    // has to live as long as the instance for messages to keep coming in
+    _debug_messenger: Option<DebugUtilsMessenger>,
 
     // some fields
     pub swapchain: Option<Arc<vulkano::swapchain::Swapchain>>,
@@ -148,152 +296,312 @@ pub struct VkImpl {
     pub pipeline: Option<Arc<GraphicsPipeline>>,
 
     pub allocators: Option<Arc<Allocators>>,
+    // staging uploads into device local buffers, see buffer.rs
+    pub uploader: Option<Arc<Uploader>>,
 
     pub renderer: Option<Arc<Mutex<Renderer>>>,
 }
 
 impl VkImpl {
-    pub async fn new(event_loop: &EventLoop<()>) -> Arc<Mutex<Self>> {
-        let library = VulkanLibrary::new().unwrap();
+    pub async fn new(
+        event_loop: &EventLoop<()>,
+        policy: DevicePolicy,
+        debug: DebugConfig,
+    ) -> Result<Arc<Mutex<Self>>, RvkpError> {
+        let library = VulkanLibrary::new()?;
 
         let required_extensions = Surface::required_extensions(&event_loop);
 
         let instance = Instance::new(
-            library,
+            library.clone(),
             InstanceCreateInfo {
                 flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
-                enabled_extensions: required_extensions,
+                enabled_layers: debug.layers(&library),
+                enabled_extensions: required_extensions.union(&debug.extensions(&library)),
                 ..Default::default()
             },
         )
-        .unwrap();
+        .map_err(RvkpError::Instance)?;
+        let debug_messenger = debug.messenger(&instance)?;
 
-        let window = Arc::new(WindowBuilder::new().build(&event_loop).unwrap());
+        let window = Arc::new(WindowBuilder::new().build(event_loop)?);
     
-        let surface = Surface::from_window(instance.clone(), window.clone()).unwrap();
+        let surface = Surface::from_window(instance.clone(), window.clone())
+            .map_err(RvkpError::Surface)?;
 
         let device_extensions = DeviceExtensions {
             khr_swapchain: true,
             ..DeviceExtensions::empty()
         };
 
-        let (physical_device, queue_family_index) = instance
-            .enumerate_physical_devices()
-            .unwrap()
-            .filter(|p| {
+        let (device, queue, transfer_queue, gpu) = Self::create_device(&instance, Some(&surface), device_extensions, &policy)?;
+
+        Ok(Arc::new(Mutex::new(Self {
+            window: Some(window),
+            surface: Some(surface),
+            device,
+            queue,
+            transfer_queue,
+            gpu,
+            extent: [0, 0],
+            vsync: Vsync::default(),
+            debug,
+            _debug_messenger: debug_messenger,
+
+            swapchain: None,
+            images: vec![],
+            render_pass: None,
+            framebuffers: vec![],
+            pipeline: None,
+
+            allocators: None,
+            uploader: None,
+
+            renderer: None,
+        })))
+    } // new
+
+    // no window, no surface and no swapchain: the renderer draws into a single offscreen
+    // color image of `extent` that can be read back with Renderer::render_offscreen
+    pub async fn headless(
+        extent: [u32; 2],
+        policy: DevicePolicy,
+        debug: DebugConfig,
+    ) -> Result<Arc<Mutex<Self>>, RvkpError> {
+        let library = VulkanLibrary::new()?;
+
+        let instance = Instance::new(
+            library.clone(),
+            InstanceCreateInfo {
+                flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+                enabled_layers: debug.layers(&library),
+                enabled_extensions: debug.extensions(&library),
+                ..Default::default()
+            },
+        )
+        .map_err(RvkpError::Instance)?;
+        let debug_messenger = debug.messenger(&instance)?;
+
+        let (device, queue, transfer_queue, gpu) = Self::create_device(&instance, None, DeviceExtensions::empty(), &policy)?;
+
+        Ok(Arc::new(Mutex::new(Self {
+            window: None,
+            surface: None,
+            device,
+            queue,
+            transfer_queue,
+            gpu,
+            extent,
+            vsync: Vsync::default(),
+            debug,
+            _debug_messenger: debug_messenger,
+
+            swapchain: None,
+            images: vec![],
+            render_pass: None,
+            framebuffers: vec![],
+            pipeline: None,
+
+            allocators: None,
+            uploader: None,
+
+            renderer: None,
+        })))
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    // picks a device with a graphics queue (that can present to `surface`, if there is one)
+    // according to `policy`
+    fn create_device(
+        instance: &Arc<Instance>,
+        surface: Option<&Arc<Surface>>,
+        device_extensions: DeviceExtensions,
+        policy: &DevicePolicy,
+    ) -> Result<DeviceQueues, RvkpError> {
+        let candidates = device::enumerate(instance)?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, p)| {
                 p.supported_extensions().contains(&device_extensions)
             })
-            .filter_map(|p| {
+            .filter_map(|(index, p)| {
                 p.queue_family_properties()
                     .iter()
                     .enumerate()
                     .position(|(i, q)| {
                         q.queue_flags.intersects(QueueFlags::GRAPHICS)
-                            && p.surface_support(i as u32, &surface).unwrap_or(false)
+                            && surface.is_none_or(|surface| {
+                                p.surface_support(i as u32, surface).unwrap_or(false)
+                            })
                     })
-                    .map(|i| (p, i as u32))
+                    .map(|i| (index, p, i as u32))
             })
-            .min_by_key(|(p, _)| {
-                match p.properties().device_type {
-                    PhysicalDeviceType::DiscreteGpu => 0,
-                    PhysicalDeviceType::IntegratedGpu => 1,
-                    PhysicalDeviceType::VirtualGpu => 2,
-                    PhysicalDeviceType::Cpu => 3,
-                    PhysicalDeviceType::Other => 4,
-                    _ => 5,
-                }
+            .collect();
+
+        let (index, physical_device, queue_family_index) = policy
+            .select(candidates)
+            .ok_or(RvkpError::NoSuitableDevice)?;
+        let gpu = GpuInfo::new(index, &physical_device);
+
+        // transfer only families are usually the gpu's copy engines, which can stream uploads
+        // without stalling rendering
+        let transfer_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| {
+                q.queue_flags.intersects(QueueFlags::TRANSFER)
+                    && !q.queue_flags.intersects(QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
             })
-            .expect("no suitable physical device found");
+            .map(|i| i as u32);
+
+        let mut queue_create_infos = vec![QueueCreateInfo {
+            queue_family_index,
+            ..Default::default()
+        }];
+        if let Some(queue_family_index) = transfer_family_index {
+            queue_create_infos.push(QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            });
+        }
 
         let (device, mut queues) = Device::new(
             physical_device,
             DeviceCreateInfo {
                 enabled_extensions: device_extensions,
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
+                queue_create_infos,
 
                 ..Default::default()
             },
         )
-        .unwrap();
+        .map_err(RvkpError::Device)?;
 
         let queue = queues.next().unwrap();
+        let transfer_queue = queues.next().unwrap_or_else(|| queue.clone());
 
-        Arc::new(Mutex::new(Self {
-            window,
-            surface,
-            device,
-            queue,
-
-            swapchain: None,
-            images: vec![],
-            render_pass: None,
-            framebuffers: vec![],
-            pipeline: None,
-
-            allocators: None,
-
-            renderer: None,
-        }))
-    } // new
+        Ok((device, queue, transfer_queue, gpu))
+    }
 
-    pub fn ignition(&mut self, renderer: Arc<Mutex<Renderer>>) {
-        self.create_swapchain();
-        self.create_render_pass();
+    pub fn ignition(&mut self, renderer: Arc<Mutex<Renderer>>) -> Result<(), RvkpError> {
         self.allocators = Some(Arc::new(Allocators::new(self.device.clone())));
+        let allocators = self.allocators.clone().unwrap();
+        self.uploader = Some(Arc::new(Uploader::new(
+            self.device.clone(),
+            self.transfer_queue.clone(),
+            self.queue.queue_family_index(),
+            allocators.memory.clone(),
+            allocators.command_buffer.clone(),
+        )));
+
+        if self.is_headless() {
+            self.images = vec![self.offscreen_image(self.extent)?];
+        } else {
+            self.create_swapchain()?;
+        }
+        self.create_render_pass()?;
         self.renderer = Some(renderer);
 
-        let (mut pipeline, mut framebuffers) = VkImpl::window_size_dependent_setup(
-            self,
-        );
+        self.create_pipeline()?;
+        self.framebuffers = VkImpl::window_size_dependent_setup(self)?;
 
-        self.pipeline = Some(pipeline);
-        self.framebuffers = framebuffers;
+        Ok(())
     }
 
-    fn create_swapchain(&mut self) {
+    fn create_swapchain(&mut self) -> Result<(), RvkpError> {
         let (swapchain, images) = {
+            let surface = self.surface.clone().unwrap();
             let surface_capabilities = self.device
                 .physical_device()
-                .surface_capabilities(&self.surface, Default::default())
-                .unwrap();
+                .surface_capabilities(&surface, Default::default())
+                .map_err(RvkpError::Swapchain)?;
             let image_format = self.device
                 .physical_device()
-                .surface_formats(&self.surface, Default::default())
-                .unwrap()[0]
+                .surface_formats(&surface, Default::default())
+                .map_err(RvkpError::Swapchain)?
+                .first()
+                .ok_or_else(|| RvkpError::Swapchain(error::invalid("surface_formats", "the surface supports no formats")))?
                 .0;
+            let composite_alpha = surface_capabilities
+                .supported_composite_alpha
+                .into_iter()
+                .next()
+                .ok_or_else(|| RvkpError::Swapchain(error::invalid(
+                    "supported_composite_alpha",
+                    "the surface supports no composite alpha mode",
+                )))?;
 
             Swapchain::new(
                 self.device.clone(),
-                self.surface.clone(),
+                surface,
                 SwapchainCreateInfo {
                     min_image_count: surface_capabilities.min_image_count.max(2),
                     image_format,
-                    image_extent: self.window.inner_size().into(),
-                    image_usage: ImageUsage::COLOR_ATTACHMENT,
-                    composite_alpha: surface_capabilities
-                        .supported_composite_alpha
-                        .into_iter()
-                        .next()
-                        .unwrap(),
+                    image_extent: self.window.clone().unwrap().inner_size().into(),
+                    // transfer src so frames can be captured, when the surface allows it
+                    image_usage: ImageUsage::COLOR_ATTACHMENT
+                        | (surface_capabilities.supported_usage_flags & ImageUsage::TRANSFER_SRC),
+                    present_mode: self.present_mode()?,
+                    composite_alpha,
                     ..Default::default()
                 },
             )
-            .unwrap()
+            .map_err(RvkpError::Swapchain)?
         };
 
+        debug::set_name(&self.device, &*swapchain, "swapchain");
+        for (i, image) in images.iter().enumerate() {
+            debug::set_name(&self.device, &**image, &format!("swapchain image {i}"));
+        }
+
         self.swapchain = Some(swapchain);
+        self.extent = [images[0].extent()[0], images[0].extent()[1]];
         self.images = images;
+
+        Ok(())
+    }
+
+    // the best mode the surface supports for the current vsync setting
+    pub fn present_mode(&self) -> Result<PresentMode, RvkpError> {
+        let supported = self.device
+            .physical_device()
+            .surface_present_modes(self.surface.as_ref().unwrap(), Default::default())
+            .map_err(RvkpError::Swapchain)?
+            .collect::<Vec<_>>();
+
+        Ok(self.vsync.present_mode(&supported))
     }
 
-    fn create_render_pass(&mut self) { 
+    // color target for headless rendering, can be copied out of
+    fn offscreen_image(&self, extent: [u32; 2]) -> Result<Arc<Image>, RvkpError> {
+        let image = Image::new(
+            self.allocators.clone().unwrap().memory.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R8G8B8A8_UNORM,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )?;
+        debug::set_name(&self.device, &*image, "offscreen image");
+
+        Ok(image)
+    }
+
+    fn create_render_pass(&mut self) -> Result<(), RvkpError> {
         let render_pass = vulkano::single_pass_renderpass!(
             self.device.clone(),
             attachments: {
                 color: {
-                    format: self.swapchain.clone().unwrap().image_format(),
+                    // swapchain format, or the offscreen image's in headless mode
+                    format: self.images[0].format(),
                     samples: 1,
                     load_op: Clear,
                     store_op: Store,
@@ -310,39 +618,97 @@ impl VkImpl {
                 depth_stencil: {depth_stencil},
             },
         )
-        .unwrap();
+        .map_err(RvkpError::Pipeline)?;
 
+        debug::set_name(&self.device, &*render_pass, "render pass");
         self.render_pass = Some(render_pass);
+
+        Ok(())
     }
 
-    pub fn window_size_dependent_setup(vk: &mut VkImpl)
-    -> (Arc<GraphicsPipeline>, Vec<Arc<Framebuffer>>) {
-        let device = vk.allocators.clone().unwrap().memory.device().clone();
+    // viewport and scissor are dynamic, so the pipeline only depends on the render pass and
+    // outlives swapchain recreation
+    fn create_pipeline(&mut self) -> Result<(), RvkpError> {
+        let device = self.device.clone();
+
+        let no_main = || RvkpError::ShaderLoad(error::invalid("entry_point", "the shader has no `main` entry point"));
+        let vs = self.renderer.clone().unwrap().lock().unwrap().shaders[0].clone().entry_point("main").ok_or_else(no_main)?;
+        let fs = self.renderer.clone().unwrap().lock().unwrap().shaders[1].clone().entry_point("main").ok_or_else(no_main)?;
+
+        let pipeline = {
+            let vertex_input_state = [RVertex3d::per_vertex(), InstanceData::per_instance()]
+                .definition(&vs.info().input_interface)
+                .map_err(|e| RvkpError::Pipeline(e.into()))?;
+            let stages = [
+                PipelineShaderStageCreateInfo::new(vs),
+                PipelineShaderStageCreateInfo::new(fs),
+            ];
+            let layout = PipelineLayout::new(
+                device.clone(),
+                PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+                    .into_pipeline_layout_create_info(device.clone())
+                    .map_err(|e| RvkpError::Pipeline(e.error))?,
+            )
+            .map_err(RvkpError::Pipeline)?;
+            let subpass = Subpass::from(self.render_pass.clone().unwrap(), 0)
+                .ok_or_else(|| RvkpError::Pipeline(error::invalid("subpass", "the render pass has no subpass 0")))?;
+
+            GraphicsPipeline::new(
+                device,
+                None,
+                GraphicsPipelineCreateInfo {
+                    stages: stages.into_iter().collect(),
+                    vertex_input_state: Some(vertex_input_state),
+                    input_assembly_state: Some(InputAssemblyState::default()),
+                    // one viewport and scissor, set every frame by set_viewport
+                    viewport_state: Some(ViewportState::default()),
+                    dynamic_state: [DynamicState::Viewport, DynamicState::Scissor]
+                        .into_iter()
+                        .collect(),
+                    rasterization_state: Some(RasterizationState::default()),
+                    depth_stencil_state: Some(DepthStencilState {
+                        depth: Some(DepthState::simple()),
+                        ..Default::default()
+                    }),
+                    multisample_state: Some(MultisampleState::default()),
+                    color_blend_state: Some(ColorBlendState::with_attachment_states(
+                        subpass.num_color_attachments(),
+                        ColorBlendAttachmentState::default(),
+                    )),
+                    subpass: Some(subpass.into()),
+                    ..GraphicsPipelineCreateInfo::layout(layout)
+                },
+            )
+            .map_err(RvkpError::Pipeline)?
+        };
 
-        let vs = vk.renderer.clone().unwrap().lock().unwrap().shaders[0].clone().entry_point("main").unwrap();
-        let fs = vk.renderer.clone().unwrap().lock().unwrap().shaders[1].clone().entry_point("main").unwrap();
+        debug::set_name(&self.device, &*pipeline, "voxel pipeline");
+        self.pipeline = Some(pipeline);
 
+        Ok(())
+    }
 
-        let extent = vk.images.clone()[0].extent();
+    // depth buffer and framebuffers, the only things that have to follow the swapchain size
+    pub fn window_size_dependent_setup(vk: &mut VkImpl) -> Result<Vec<Arc<Framebuffer>>, RvkpError> {
+        let depth_image = Image::new(
+            vk.allocators.clone().unwrap().memory.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::D16_UNORM,
+                extent: vk.images.clone()[0].extent(),
+                usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )?;
+        debug::set_name(&vk.device, &*depth_image, "depth buffer");
 
-        let depth_buffer = ImageView::new_default(
-            Image::new(
-                vk.allocators.clone().unwrap().memory.clone(),
-                ImageCreateInfo {
-                    image_type: ImageType::Dim2d,
-                    format: Format::D16_UNORM,
-                     extent: vk.images.clone()[0].extent(),
-                    usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
-                    ..Default::default()
-                },
-                AllocationCreateInfo::default(),
-            ).unwrap(),
-        ).unwrap();
+        let depth_buffer = ImageView::new_default(depth_image).map_err(RvkpError::Pipeline)?;
 
-        let framebuffers = vk.images.clone()
+        vk.images.clone()
             .iter()
             .map(|image| {
-                let view = ImageView::new_default(image.clone()).unwrap();
+                let view = ImageView::new_default(image.clone()).map_err(RvkpError::Pipeline)?;
                 Framebuffer::new(
                     vk.render_pass.clone().unwrap(),
                     FramebufferCreateInfo {
@@ -350,94 +716,67 @@ impl VkImpl {
                         ..Default::default()
                     },
                 )
-                .unwrap()
+                .map_err(RvkpError::Pipeline)
             })
-            .collect::<Vec<_>>();
+            .collect()
+    }
 
-            let pipeline = {
-                let vertex_input_state = [RVertex3d::per_vertex()]
-                    .definition(&vs.info().input_interface)
-                    .unwrap();
-                let stages = [
-                    PipelineShaderStageCreateInfo::new(vs),
-                    PipelineShaderStageCreateInfo::new(fs),
-                ];
-                let layout = PipelineLayout::new(
-                    device.clone(),
-                    PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
-                        .into_pipeline_layout_create_info(device.clone())
-                        .unwrap(),
-                )
-                .unwrap();
-                let subpass = Subpass::from(vk.render_pass.clone().unwrap(), 0).unwrap();
-        
-                GraphicsPipeline::new(
-                    device,
-                    None,
-                    GraphicsPipelineCreateInfo {
-                        stages: stages.into_iter().collect(),
-                        vertex_input_state: Some(vertex_input_state),
-                        input_assembly_state: Some(InputAssemblyState::default()),
-                        viewport_state: Some(ViewportState {
-                            viewports: [Viewport {
-                                offset: [0.0, 0.0],
-                                extent: [extent[0] as f32, extent[1] as f32],
-                                depth_range: 0.0..=1.0,
-                            }]
-                            .into_iter()
-                            .collect(),
-                            ..Default::default()
-                        }),
-                        rasterization_state: Some(RasterizationState::default()),
-                        depth_stencil_state: Some(DepthStencilState {
-                            depth: Some(DepthState::simple()),
-                            ..Default::default()
-                        }),
-                        multisample_state: Some(MultisampleState::default()),
-                        color_blend_state: Some(ColorBlendState::with_attachment_states(
-                            subpass.num_color_attachments(),
-                            ColorBlendAttachmentState::default(),
-                        )),
-                        subpass: Some(subpass.into()),
-                        ..GraphicsPipelineCreateInfo::layout(layout)
-                    },
-                )
-                .unwrap()
-            };
+    // covers the whole render target, recorded at the start of every frame
+    pub fn set_viewport<L, A: CommandBufferAllocator>(&self, builder: &mut AutoCommandBufferBuilder<L, A>) {
+        builder
+            .set_viewport(0, [Viewport {
+                offset: [0.0, 0.0],
+                extent: [self.extent[0] as f32, self.extent[1] as f32],
+                depth_range: 0.0..=1.0,
+            }].into_iter().collect())
+            .unwrap()
+            .set_scissor(0, [Scissor {
+                offset: [0, 0],
+                extent: self.extent,
+            }].into_iter().collect())
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        (pipeline, framebuffers)
+    // what Renderer::new builds, the swapchain doesn't exist yet
+    fn presenter_before_ignition() -> VkPresenter {
+        VkPresenter {
+            recreate_swapchain: false,
+            frames_in_flight: 0,
+            fences: vec![],
+            previous_fence_i: 0,
+        }
     }
 
-    pub fn vertex_buffer(&self, vertices: Vec<RVertex3d>) -> Subbuffer<[RVertex3d]> {
-        Buffer::from_iter(
-            self.allocators.clone().unwrap().memory.clone(), 
-            BufferCreateInfo {
-                usage: BufferUsage::VERTEX_BUFFER,
-                ..Default::default() 
-            }, 
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            }, 
-            vertices,
-        ).unwrap()
+    #[test]
+    fn first_frame_gets_a_fence_per_image() {
+        let mut presenter = presenter_before_ignition();
+        presenter.fit_images(3);
+
+        assert_eq!(presenter.frames_in_flight, 3);
+        assert_eq!(presenter.fences.len(), 3);
+        assert!(presenter.fences.iter().all(|f| f.is_none()));
+        // submit looks up the previous frame's fence
+        assert!((presenter.previous_fence_i as usize) < presenter.fences.len());
     }
 
-    pub fn index_buffer(&self, indices: Vec<u32>) -> Subbuffer<[u32]> {
-        Buffer::from_iter(
-            self.allocators.clone().unwrap().memory.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::INDEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
-                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            indices,
-        )
-        .unwrap()
+    #[test]
+    fn fences_follow_the_image_count() {
+        let mut presenter = presenter_before_ignition();
+        presenter.fit_images(3);
+        presenter.previous_fence_i = 2;
+
+        presenter.fit_images(2);
+        assert_eq!(presenter.fences.len(), 2);
+        assert_eq!(presenter.previous_fence_i, 0);
+
+        // same count, nothing is reset
+        presenter.previous_fence_i = 1;
+        presenter.fit_images(2);
+        assert_eq!(presenter.previous_fence_i, 1);
     }
 }
@@ -1,31 +1,60 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use vulkano::{buffer::BufferContents, command_buffer::{AutoCommandBufferBuilder, RenderPassBeginInfo}, descriptor_set::WriteDescriptorSet, pipeline::{graphics::vertex_input::Vertex, Pipeline, PipelineBindPoint}, shader::{EntryPoint, ShaderModule}, swapchain::{acquire_next_image, SwapchainCreateInfo}, Validated};
+use vulkano::{buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer}, format::Format, image::ImageUsage, command_buffer::{allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CopyImageToBufferInfo, PrimaryAutoCommandBuffer, RenderPassBeginInfo}, memory::allocator::{AllocationCreateInfo, MemoryTypeFilter}, render_pass::Framebuffer, sync::{self, GpuFuture}, descriptor_set::WriteDescriptorSet, pipeline::{graphics::vertex_input::Vertex, Pipeline, PipelineBindPoint}, shader::{EntryPoint, ShaderModule}, swapchain::{acquire_next_image, SwapchainCreateInfo}, Validated};
 use winit::{event::{Event, WindowEvent}, event_loop::EventLoop};
 
 use crate::utils::random;
+use crate::world::ChunkCoord;
 
-use super::{camera::Camera, mesh::Mesh, shader, vk_impl::{VkImpl, VkPresenter}};
+use super::{capture, camera::Camera, debug, error::RvkpError, mesh::Mesh, scene::{NodeId, Scene}, shader, vk_impl::{VkImpl, VkPresenter}};
 
 #[repr(C)]
 #[derive(BufferContents, Vertex)]
 pub struct RVertex3d {
     #[format(R32G32B32_SFLOAT)]
     position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    color: [f32; 3],
 }
 
 impl RVertex3d {
     pub fn new(x: f32, y: f32, z: f32) -> Self {
         RVertex3d {
             position: [x, y, z],
+            color: [1.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn colored(x: f32, y: f32, z: f32, color: [f32; 3]) -> Self {
+        RVertex3d {
+            position: [x, y, z],
+            color,
         }
     }
 }
 
+// per instance attributes, every mesh is drawn with at least one
+#[repr(C)]
+#[derive(BufferContents, Vertex, Default)]
+pub struct InstanceData {
+    #[format(R32G32B32_SFLOAT)]
+    pub ofs: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub fun_factor: [f32; 3],
+}
+
 pub struct Renderer {
     pub vk_impl: Arc<Mutex<VkImpl>>,
     
-    pub meshes: Vec<Mesh>,
+    pub scene: Scene,
+    // chunk meshes all hang off chunk_root, see view_utils
+    pub chunk_root: NodeId,
+    pub chunk_nodes: HashMap<ChunkCoord, NodeId>,
+    // remeshed chunks waiting for their upload, the old mesh is drawn until then
+    pub pending_chunks: HashMap<ChunkCoord, Mesh>,
     pub shaders: Vec<Arc<ShaderModule>>,
 
     pub presenter: VkPresenter,
@@ -34,40 +63,196 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub async fn new(vk_impl: Arc<Mutex<VkImpl>>) -> Arc<Mutex<Self>> {
+    pub async fn new(vk_impl: Arc<Mutex<VkImpl>>) -> Result<Arc<Mutex<Self>>, RvkpError> {
         let mut vk_clone = vk_impl.clone();
         let mut vk = vk_clone.lock().unwrap();
         let shaders = vec![
-            shader::vs::load(vk.device.clone()).unwrap(),
-            shader::fs::load(vk.device.clone()).unwrap(),
+            shader::vs::load(vk.device.clone()).map_err(RvkpError::ShaderLoad)?,
+            shader::fs::load(vk.device.clone()).map_err(RvkpError::ShaderLoad)?,
         ];
         let presenter = VkPresenter::new(&vk);
         let camera = Camera::new();
 
-        Arc::new(Mutex::new(Self {
+        let mut scene = Scene::new();
+        let chunk_root = scene.add(scene.root(), "chunks");
+
+        Ok(Arc::new(Mutex::new(Self {
             vk_impl,
-            meshes: vec![],
+            scene,
+            chunk_root,
+            chunk_nodes: HashMap::new(),
+            pending_chunks: HashMap::new(),
             shaders,
             presenter,
             camera,
-        }))
+        })))
     }
     
-    pub fn update(&mut self) {
-        let mut vk = self.vk_impl.lock().unwrap();
+    // errors if the swapchain couldn't be recreated or the frame couldn't be recorded or submitted
+    pub fn update(&mut self) -> Result<(), RvkpError> {
+        let vk_impl = self.vk_impl.clone();
+        let mut vk = vk_impl.lock().unwrap();
+
+        // minimized
+        if !self.presenter.if_recreate_swapchain(&mut vk)? {
+            return Ok(());
+        }
+
+        // everything uploaded since the last frame goes out in one batch, meshes show up once
+        // their batch is done
+        vk.uploader.clone().unwrap().flush()?;
+        self.swap_uploaded_chunks();
+
+        // recorded only once we know which image, and so which framebuffer, this frame uses
+        let Some((image_index, acquire_future)) = self.presenter.acquire(&vk)? else {
+            return Ok(());
+        };
+
+        let builder = self.record(&vk, vk.framebuffers[image_index as usize].clone())?;
+        let command_buffer = builder.build().map_err(RvkpError::Submit)?;
+
+        self.presenter.submit(&vk, image_index, acquire_future, command_buffer)?;
+        vk.debug.check();
+
+        Ok(())
+    }
+
+    // draws one frame into the offscreen image and reads it back, tightly packed rows of
+    // RGBA8 pixels. only available on a VkImpl created with VkImpl::headless
+    pub fn render_offscreen(&mut self) -> Result<Vec<u8>, RvkpError> {
+        let vk_impl = self.vk_impl.clone();
+        let vk = vk_impl.lock().unwrap();
+        if !vk.is_headless() {
+            return Err(RvkpError::CaptureUnavailable("offscreen rendering needs a headless VkImpl"));
+        }
+
+        let readback = Self::readback_buffer(&vk, vk.images[0].format())?;
+
+        // frames have to be reproducible, so wait for the uploads instead of skipping meshes
+        let uploader = vk.uploader.clone().unwrap();
+        uploader.wait(&uploader.flush()?)?;
+
+        let mut builder = self.record(&vk, vk.framebuffers[0].clone())?;
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                vk.images[0].clone(),
+                readback.clone(),
+            ))
+            .map_err(|e| RvkpError::Submit(e.into()))?;
+        let command_buffer = builder.build().map_err(RvkpError::Submit)?;
+
+        sync::now(vk.device.clone())
+            .then_execute(vk.queue.clone(), command_buffer)
+            .map_err(RvkpError::Execute)?
+            .then_signal_fence_and_flush()
+            .map_err(RvkpError::Submit)?
+            .wait(None)
+            .map_err(RvkpError::Submit)?;
+
+        vk.debug.check();
+
+        let pixels = readback.read().unwrap().to_vec();
+        Ok(pixels)
+    }
+
+    // renders a frame and writes it to `path` as a PNG, from the swapchain or from the
+    // offscreen image in headless mode
+    pub fn capture_frame(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let headless = self.vk_impl.lock().unwrap().is_headless();
 
-        self.presenter.if_recreate_swapchain(&mut vk);
+        let (pixels, format, [width, height]) = if headless {
+            let pixels = self.render_offscreen().map_err(io::Error::other)?;
+            let vk = self.vk_impl.lock().unwrap();
 
+            (pixels, vk.images[0].format(), vk.extent)
+        } else {
+            self.capture_swapchain().map_err(io::Error::other)?
+        };
+
+        let (rgba, srgb) = capture::to_rgba8(format, pixels).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, format!("can't capture {format:?} frames"))
+        })?;
+
+        capture::write_png(path.as_ref(), width, height, &rgba, srgb)
+    }
+
+    // renders and presents a frame like update() does, copying the image out before presenting
+    fn capture_swapchain(&mut self) -> Result<(Vec<u8>, Format, [u32; 2]), RvkpError> {
+        let vk_impl = self.vk_impl.clone();
+        let mut vk = vk_impl.lock().unwrap();
+
+        if !self.presenter.if_recreate_swapchain(&mut vk)? {
+            return Err(RvkpError::CaptureUnavailable("the window is minimized"));
+        }
+
+        // checked before anything is recorded, 10 bit and HDR surfaces can't become a PNG
+        let format = vk.images[0].format();
+        if !capture::supported(format) {
+            return Err(RvkpError::UnsupportedFormat(format));
+        }
+
+        if !vk.images[0].usage().intersects(ImageUsage::TRANSFER_SRC) {
+            return Err(RvkpError::CaptureUnavailable(
+                "the surface doesn't allow copying out of swapchain images",
+            ));
+        }
+
+        let Some((image_index, acquire_future)) = self.presenter.acquire(&vk)? else {
+            return Err(RvkpError::CaptureUnavailable("the swapchain is out of date, try again next frame"));
+        };
+
+        let image = vk.images[image_index as usize].clone();
+        let readback = Self::readback_buffer(&vk, format)?;
+
+        let mut builder = self.record(&vk, vk.framebuffers[image_index as usize].clone())?;
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image.clone(), readback.clone()))
+            .map_err(|e| RvkpError::Submit(e.into()))?;
+        let command_buffer = builder.build().map_err(RvkpError::Submit)?;
+
+        self.presenter.submit(&vk, image_index, acquire_future, command_buffer)?;
+        vk.queue
+            .with(|mut queue| queue.wait_idle())
+            .map_err(|e| RvkpError::Submit(e.into()))?;
+
+        let pixels = readback.read().unwrap().to_vec();
+        Ok((pixels, format, vk.extent))
+    }
+
+    // host visible buffer big enough for one frame of `format`
+    fn readback_buffer(vk: &VkImpl, format: Format) -> Result<Subbuffer<[u8]>, RvkpError> {
+        let [width, height] = vk.extent;
+
+        let buffer = Buffer::new_slice::<u8>(
+            vk.allocators.clone().unwrap().memory.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            width as u64 * height as u64 * format.block_size(),
+        )?;
+        debug::set_name(&vk.device, &**buffer.buffer(), "readback buffer");
+
+        Ok(buffer)
+    }
+
+    // records the render pass for one frame into `framebuffer`
+    fn record(
+        &mut self,
+        vk: &VkImpl,
+        framebuffer: Arc<Framebuffer>,
+    ) -> Result<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>, Arc<StandardCommandBufferAllocator>>, RvkpError> {
         let mut builder = AutoCommandBufferBuilder::primary(
             &vk.allocators.clone().unwrap().command_buffer,
             vk.queue.queue_family_index(),
             vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit,
         )
-        .unwrap();
-
-        let mesh_zero = || {
-            self.meshes[0].clone()
-        };
+        .map_err(RvkpError::Submit)?;
 
         builder
             .begin_render_pass(
@@ -76,28 +261,32 @@ impl Renderer {
                         Some([0.0, 0.0, 1.0, 1.0].into()),
                         Some(1f32.into()),
                     ],
-                    ..RenderPassBeginInfo::framebuffer(vk.framebuffers[0].clone())
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
                 },
                 Default::default(),
             )
-            .unwrap()
-            .bind_pipeline_graphics(vk.pipeline.clone().unwrap().clone())
-            .unwrap();
+            .map_err(|e| RvkpError::Submit(e.into()))?;
+
+        self.camera.set_extent(vk.extent);
+        self.scene.update();
+
+        // an empty scene is just the clear color
+        if !self.scene.is_empty() {
+            let pipeline = vk.pipeline.clone().unwrap();
+            builder
+                .bind_pipeline_graphics(pipeline.clone())
+                .map_err(|e| RvkpError::Submit(e.into()))?;
+            vk.set_viewport(&mut builder);
 
-        let mut builder = self.camera.send_push_constants(builder, vk.pipeline.clone().unwrap().layout());
+            builder = self.camera.send_push_constants(builder, pipeline.layout());
+
+            self.scene.draw(&mut builder, pipeline.layout());
+        }
 
         builder
-            .bind_vertex_buffers(0, mesh_zero().vert_buf.unwrap().clone())
-            .unwrap()
-            .bind_index_buffer(mesh_zero().ind_buf.unwrap().clone())
-            .unwrap()
-            .draw_indexed(mesh_zero().ind_buf.unwrap().len() as u32, 1, 0, 0, 0)
-            .unwrap()
             .end_render_pass(Default::default())
-            .unwrap();
+            .map_err(|e| RvkpError::Submit(e.into()))?;
 
-        let command_buffer = builder.build().unwrap();
-
-        self.presenter.present(&vk, command_buffer);
+        Ok(builder)
     }
-}
\ No newline at end of file
+}
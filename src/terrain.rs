@@ -0,0 +1,352 @@
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+
+use crate::utils::{idx_to_vec3, vec3_to_idx};
+use crate::voxel::{VoxelId, AIR, DIRT, GRASS, STONE};
+
+// decides what every voxel of the world is made of. implementations must be pure functions of
+// the seed and the world-space voxel coordinate, so the same seed always gives the same world
+pub trait TerrainGenerator: Send + Sync {
+    fn voxel(&self, seed: u64, pos: [i32; 3]) -> VoxelId;
+
+    // voxels of the chunk at `chunk` (in chunk coordinates), laid out like utils::vec3_to_idx
+    fn fill_chunk(&self, seed: u64, chunk: [i32; 3], size: usize) -> Vec<VoxelId> {
+        let origin = chunk.map(|c| c * size as i32);
+
+        (0..size * size * size)
+            .map(|i| {
+                let local = idx_to_vec3(i, size);
+                self.voxel(seed, [
+                    origin[0] + local.x as i32,
+                    origin[1] + local.y as i32,
+                    origin[2] + local.z as i32,
+                ])
+            })
+            .collect()
+    }
+}
+
+// everything below `height` is `material`
+pub struct FlatWorld {
+    pub height: i32,
+    pub material: VoxelId,
+}
+
+impl TerrainGenerator for FlatWorld {
+    fn voxel(&self, _seed: u64, pos: [i32; 3]) -> VoxelId {
+        if pos[1] < self.height { self.material } else { AIR }
+    }
+}
+
+// rolling hills from fractal 2d value noise, grass on top of a few layers of dirt over stone
+pub struct Heightmap {
+    pub base_height: f32,
+    pub amplitude: f32,
+    pub scale: f32,
+    pub octaves: u32,
+}
+
+impl Default for Heightmap {
+    fn default() -> Self {
+        Self {
+            base_height: 16.0,
+            amplitude: 24.0,
+            scale: 64.0,
+            octaves: 4,
+        }
+    }
+}
+
+impl Heightmap {
+    pub fn height_at(&self, seed: u64, x: i32, z: i32) -> f32 {
+        let n = fbm(self.octaves, |f| {
+            value_noise_2d(seed, x as f32 * f / self.scale, z as f32 * f / self.scale)
+        });
+
+        self.base_height + (n * 2.0 - 1.0) * self.amplitude
+    }
+}
+
+impl Heightmap {
+    // material `depth` voxels below the surface, air above it
+    fn layer(depth: f32) -> VoxelId {
+        match depth {
+            d if d <= 0.0 => AIR,
+            d if d <= 1.0 => GRASS,
+            d if d <= 4.0 => DIRT,
+            _ => STONE,
+        }
+    }
+}
+
+impl TerrainGenerator for Heightmap {
+    fn voxel(&self, seed: u64, pos: [i32; 3]) -> VoxelId {
+        Self::layer(self.height_at(seed, pos[0], pos[2]) - pos[1] as f32)
+    }
+
+    // the noise only depends on x and z, so it's sampled once per column instead of per voxel
+    fn fill_chunk(&self, seed: u64, chunk: [i32; 3], size: usize) -> Vec<VoxelId> {
+        let origin = chunk.map(|c| c * size as i32);
+        let mut voxels = vec![AIR; size * size * size];
+
+        for z in 0..size {
+            for x in 0..size {
+                let height = self.height_at(seed, origin[0] + x as i32, origin[2] + z as i32);
+
+                for y in 0..size {
+                    voxels[vec3_to_idx(x, y, z, size)] = Self::layer(height - (origin[1] + y as i32) as f32);
+                }
+            }
+        }
+
+        voxels
+    }
+}
+
+// `material` wherever fractal 3d value noise is above `threshold`, gives floating islands and overhangs
+pub struct Noise3d {
+    pub scale: f32,
+    pub threshold: f32,
+    pub octaves: u32,
+    pub material: VoxelId,
+}
+
+impl Default for Noise3d {
+    fn default() -> Self {
+        Self {
+            scale: 24.0,
+            threshold: 0.55,
+            octaves: 3,
+            material: STONE,
+        }
+    }
+}
+
+impl TerrainGenerator for Noise3d {
+    fn voxel(&self, seed: u64, pos: [i32; 3]) -> VoxelId {
+        let p = pos.map(|c| c as f32 / self.scale);
+        let n = fbm(self.octaves, |f| value_noise_3d(seed, p[0] * f, p[1] * f, p[2] * f));
+
+        if n > self.threshold { self.material } else { AIR }
+    }
+}
+
+// mixed into the seed so the caves don't follow the surface noise
+const CAVE_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// carves tunnels out of another generator where 3d noise falls in a narrow band around 0.5
+pub struct Caves<G: TerrainGenerator> {
+    pub terrain: G,
+    pub scale: f32,
+    pub width: f32,
+}
+
+impl<G: TerrainGenerator> Caves<G> {
+    pub fn new(terrain: G) -> Self {
+        Self {
+            terrain,
+            scale: 16.0,
+            width: 0.04,
+        }
+    }
+}
+
+impl<G: TerrainGenerator> TerrainGenerator for Caves<G> {
+    fn voxel(&self, seed: u64, pos: [i32; 3]) -> VoxelId {
+        let voxel = self.terrain.voxel(seed, pos);
+        if voxel == AIR {
+            return AIR;
+        }
+
+        let p = pos.map(|c| c as f32 / self.scale);
+        let n = value_noise_3d(seed ^ CAVE_SEED, p[0], p[1], p[2]);
+
+        if (n - 0.5).abs() > self.width { voxel } else { AIR }
+    }
+
+    // keeps whatever shortcut the wrapped generator has for whole chunks
+    fn fill_chunk(&self, seed: u64, chunk: [i32; 3], size: usize) -> Vec<VoxelId> {
+        let origin = chunk.map(|c| c * size as i32);
+        let mut voxels = self.terrain.fill_chunk(seed, chunk, size);
+
+        for (i, voxel) in voxels.iter_mut().enumerate() {
+            if *voxel == AIR {
+                continue;
+            }
+
+            let local = idx_to_vec3(i, size);
+            let p = [
+                (origin[0] + local.x as i32) as f32 / self.scale,
+                (origin[1] + local.y as i32) as f32 / self.scale,
+                (origin[2] + local.z as i32) as f32 / self.scale,
+            ];
+            let n = value_noise_3d(seed ^ CAVE_SEED, p[0], p[1], p[2]);
+
+            if (n - 0.5).abs() <= self.width {
+                *voxel = AIR;
+            }
+        }
+
+        voxels
+    }
+}
+
+// wraps any fn(seed, world_pos) -> voxel
+pub struct FnGenerator<F: Fn(u64, [i32; 3]) -> VoxelId + Send + Sync>(pub F);
+
+impl<F: Fn(u64, [i32; 3]) -> VoxelId + Send + Sync> TerrainGenerator for FnGenerator<F> {
+    fn voxel(&self, seed: u64, pos: [i32; 3]) -> VoxelId {
+        (self.0)(seed, pos)
+    }
+}
+
+// a generator together with the seed it runs with
+#[derive(Clone)]
+pub struct Terrain {
+    pub seed: u64,
+    pub generator: Arc<dyn TerrainGenerator>,
+}
+
+impl Terrain {
+    pub fn new(seed: u64, generator: impl TerrainGenerator + 'static) -> Self {
+        Self {
+            seed,
+            generator: Arc::new(generator),
+        }
+    }
+
+    pub fn chunk(&self, coord: [i32; 3], size: usize) -> Vec<VoxelId> {
+        self.generator.fill_chunk(self.seed, coord, size)
+    }
+
+    pub fn voxel(&self, pos: [i32; 3]) -> VoxelId {
+        self.generator.voxel(self.seed, pos)
+    }
+}
+
+pub static TERRAIN: Lazy<RwLock<Terrain>> = Lazy::new(|| {
+    RwLock::new(Terrain::new(0, Heightmap::default()))
+});
+
+// replaces the generator used for chunks generated from now on
+pub fn set_terrain(terrain: Terrain) {
+    *TERRAIN.write().unwrap() = terrain;
+}
+
+// NOISE //
+
+// splitmix64 finalizer over the seed and lattice point
+fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut h = seed
+        ^ (x as u32 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u32 as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ (z as u32 as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+// uniform value in [0, 1) at a lattice point
+fn lattice(seed: u64, x: i32, y: i32, z: i32) -> f32 {
+    (hash(seed, x, y, z) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+pub fn value_noise_2d(seed: u64, x: f32, z: f32) -> f32 {
+    value_noise_3d(seed, x, 0.0, z)
+}
+
+pub fn value_noise_3d(seed: u64, x: f32, y: f32, z: f32) -> f32 {
+    let (x0, y0, z0) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+    let (tx, ty, tz) = (smooth(x - x0 as f32), smooth(y - y0 as f32), smooth(z - z0 as f32));
+
+    let l = |dx: i32, dy: i32, dz: i32| lattice(seed, x0 + dx, y0 + dy, z0 + dz);
+
+    let x00 = lerp(l(0, 0, 0), l(1, 0, 0), tx);
+    let x10 = lerp(l(0, 1, 0), l(1, 1, 0), tx);
+    let x01 = lerp(l(0, 0, 1), l(1, 0, 1), tx);
+    let x11 = lerp(l(0, 1, 1), l(1, 1, 1), tx);
+
+    lerp(lerp(x00, x10, ty), lerp(x01, x11, ty), tz)
+}
+
+// sums `octaves` layers of noise at doubling frequency and halving weight, normalized to [0, 1)
+fn fbm(octaves: u32, noise: impl Fn(f32) -> f32) -> f32 {
+    let mut sum = 0.0;
+    let mut weight = 1.0;
+    let mut total = 0.0;
+    let mut freq = 1.0;
+
+    for _ in 0..octaves.max(1) {
+        sum += noise(freq) * weight;
+        total += weight;
+        weight *= 0.5;
+        freq *= 2.0;
+    }
+
+    sum / total
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_gen::CHUNK_SIZE;
+
+    fn generators() -> Vec<Terrain> {
+        vec![
+            Terrain::new(7, Heightmap::default()),
+            Terrain::new(7, Noise3d::default()),
+            Terrain::new(7, Caves::new(Heightmap::default())),
+            Terrain::new(7, FnGenerator(|seed, pos: [i32; 3]| {
+                if (hash(seed, pos[0], pos[1], pos[2]) & 1) == 0 { STONE } else { AIR }
+            })),
+        ]
+    }
+
+    #[test]
+    fn same_seed_same_chunk() {
+        for terrain in generators() {
+            let again = Terrain { seed: terrain.seed, generator: terrain.generator.clone() };
+
+            assert_eq!(terrain.chunk([1, 0, -2], CHUNK_SIZE), again.chunk([1, 0, -2], CHUNK_SIZE));
+        }
+    }
+
+    #[test]
+    fn different_seed_different_chunk() {
+        for terrain in generators() {
+            let other = Terrain { seed: terrain.seed + 1, generator: terrain.generator.clone() };
+
+            assert_ne!(terrain.chunk([1, 0, -2], CHUNK_SIZE), other.chunk([1, 0, -2], CHUNK_SIZE));
+        }
+    }
+
+    #[test]
+    fn fill_chunk_matches_voxel() {
+        let chunk = [-1, 0, 3];
+        let origin = chunk.map(|c| c * CHUNK_SIZE as i32);
+
+        for terrain in generators() {
+            let voxels = terrain.chunk(chunk, CHUNK_SIZE);
+
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let pos = [origin[0] + x as i32, origin[1] + y as i32, origin[2] + z as i32];
+                        assert_eq!(voxels[vec3_to_idx(x, y, z, CHUNK_SIZE)], terrain.voxel(pos), "{pos:?}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+
+// index into the voxel registry, this is what chunks store per voxel
+pub type VoxelId = u16;
+
+pub const AIR: VoxelId = 0;
+pub const STONE: VoxelId = 1;
+pub const DIRT: VoxelId = 2;
+pub const GRASS: VoxelId = 3;
+pub const SAND: VoxelId = 4;
+pub const WATER: VoxelId = 5;
+pub const GLASS: VoxelId = 6;
+
+#[derive(Debug, Clone)]
+pub struct VoxelType {
+    pub id: VoxelId,
+    pub name: String,
+    // blocks movement and picking
+    pub solid: bool,
+    // faces behind it stay visible
+    pub transparent: bool,
+    pub color: [f32; 3],
+    pub texture: Option<u32>,
+}
+
+pub struct VoxelRegistry {
+    types: Vec<VoxelType>,
+}
+
+impl VoxelRegistry {
+    // registry with only air in it
+    pub fn new() -> Self {
+        let mut registry = Self { types: vec![] };
+        registry.register("air", false, true, [0.0, 0.0, 0.0]);
+
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, solid: bool, transparent: bool, color: [f32; 3]) -> VoxelId {
+        let id = self.types.len() as VoxelId;
+        self.types.push(VoxelType {
+            id,
+            name: name.to_string(),
+            solid,
+            transparent,
+            color,
+            texture: None,
+        });
+
+        id
+    }
+
+    pub fn texture(&mut self, id: VoxelId, texture: u32) {
+        self.types[id as usize].texture = Some(texture);
+    }
+
+    pub fn get(&self, id: VoxelId) -> &VoxelType {
+        // unknown ids fall back to air rather than panicking in the mesher
+        self.types.get(id as usize).unwrap_or(&self.types[AIR as usize])
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&VoxelType> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    pub fn is_solid(&self, id: VoxelId) -> bool {
+        self.get(id).solid
+    }
+
+    pub fn is_opaque(&self, id: VoxelId) -> bool {
+        id != AIR && !self.get(id).transparent
+    }
+
+    // whether the face of `voxel` that touches `neighbour` can be seen
+    pub fn face_visible(&self, voxel: VoxelId, neighbour: VoxelId) -> bool {
+        voxel != AIR && !self.is_opaque(neighbour) && voxel != neighbour
+    }
+}
+
+impl Default for VoxelRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+
+        // order has to match the constants above
+        registry.register("stone", true, false, [0.5, 0.5, 0.52]);
+        registry.register("dirt", true, false, [0.45, 0.3, 0.18]);
+        registry.register("grass", true, false, [0.3, 0.6, 0.2]);
+        registry.register("sand", true, false, [0.85, 0.8, 0.55]);
+        registry.register("water", false, true, [0.2, 0.35, 0.8]);
+        registry.register("glass", true, true, [0.8, 0.9, 0.95]);
+
+        registry
+    }
+}
+
+pub static VOXEL_TYPES: Lazy<RwLock<VoxelRegistry>> = Lazy::new(|| {
+    RwLock::new(VoxelRegistry::default())
+});
@@ -0,0 +1,695 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use glam::Vec3A;
+use once_cell::sync::Lazy;
+
+use crate::mesh_gen::{VoxelMeshGenJob, CHUNK_SIZE, NEIGHBOUR_OFFSETS};
+use crate::region;
+use crate::terrain::{set_terrain, Terrain, TERRAIN};
+use crate::utils::vec3_to_idx;
+use crate::voxel::{VoxelId, AIR, VOXEL_TYPES};
+
+pub type ChunkCoord = [i32; 3];
+
+// where the world is saved unless --world=<dir> says otherwise
+pub const DEFAULT_WORLD_DIR: &str = "world";
+pub const DEFAULT_RADIUS: i32 = 4;
+
+// what changed after the camera moved
+#[derive(Debug, Default)]
+pub struct StreamUpdate {
+    // chunks that came into range, closest first. they have to be loaded off the calling thread
+    // with load_job and handed back through insert_chunk
+    pub load: Vec<ChunkCoord>,
+    // dropped chunks, including ones that were still loading
+    pub unloaded: Vec<ChunkCoord>,
+}
+
+// reads a chunk from the save directory or generates it, whatever it takes to get its voxels.
+// carries everything it needs so it can run on a worker without the world lock
+pub struct ChunkLoadJob {
+    pub coord: ChunkCoord,
+    terrain: Terrain,
+    save_dir: Option<PathBuf>,
+}
+
+impl ChunkLoadJob {
+    pub fn run(&self) -> Vec<VoxelId> {
+        self.load().unwrap_or_else(|| self.terrain.chunk(self.coord, CHUNK_SIZE))
+    }
+
+    // the saved copy of the chunk, if there is one
+    fn load(&self) -> Option<Vec<VoxelId>> {
+        let dir = self.save_dir.as_ref()?;
+
+        match region::load_chunk(dir, self.coord) {
+            Ok(voxels) => voxels,
+            Err(e) => {
+                println!("failed to load chunk {:?}, regenerating it: {e}", self.coord);
+                None
+            }
+        }
+    }
+}
+
+// owns the voxel data of every loaded chunk
+pub struct World {
+    chunks: HashMap<ChunkCoord, Arc<Vec<VoxelId>>>,
+    // in range and handed out through StreamUpdate::load, but not inserted yet
+    loading: HashSet<ChunkCoord>,
+    // edited chunks whose meshes are out of date
+    dirty: HashSet<ChunkCoord>,
+    // edited since they were loaded, written back to disk when unloaded
+    modified: HashSet<ChunkCoord>,
+
+    // region files are read from and written to here, None keeps the world in memory only
+    pub save_dir: Option<PathBuf>,
+
+    // in chunks, around the chunk the camera is in
+    pub radius: i32,
+    // chunks further than this are dropped, kept a bit larger than radius so chunks
+    // on the edge don't load and unload every time the camera crosses a border
+    pub unload_radius: i32,
+
+    center: Option<ChunkCoord>,
+}
+
+impl World {
+    pub fn new(radius: i32) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            loading: HashSet::new(),
+            dirty: HashSet::new(),
+            modified: HashSet::new(),
+            save_dir: None,
+            radius,
+            unload_radius: radius + 1,
+            center: None,
+        }
+    }
+
+    pub fn chunk_coord(pos: Vec3A) -> ChunkCoord {
+        let size = CHUNK_SIZE as f32;
+        [
+            (pos.x / size).floor() as i32,
+            (pos.y / size).floor() as i32,
+            (pos.z / size).floor() as i32,
+        ]
+    }
+
+    pub fn chunk(&self, coord: ChunkCoord) -> Option<Arc<Vec<VoxelId>>> {
+        self.chunks.get(&coord).cloned()
+    }
+
+    pub fn is_loaded(&self, coord: ChunkCoord) -> bool {
+        self.chunks.contains_key(&coord)
+    }
+
+    pub fn loaded(&self) -> impl Iterator<Item = &ChunkCoord> {
+        self.chunks.keys()
+    }
+
+    // asks for every chunk within `radius` of the camera to be loaded and unloads the ones past
+    // `unload_radius`. does nothing until the camera enters another chunk
+    pub fn update(&mut self, camera_pos: Vec3A) -> StreamUpdate {
+        let center = Self::chunk_coord(camera_pos);
+        let mut update = StreamUpdate::default();
+
+        if self.center == Some(center) {
+            return update;
+        }
+        self.center = Some(center);
+
+        let dist2 = |c: &ChunkCoord| {
+            let d = [c[0] - center[0], c[1] - center[1], c[2] - center[2]];
+            d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+        };
+
+        let far = self.unload_radius * self.unload_radius;
+        let dirty = &mut self.dirty;
+        let mut evicted = vec![];
+        self.chunks.retain(|coord, voxels| {
+            let keep = dist2(coord) <= far;
+            if !keep {
+                dirty.remove(coord);
+                update.unloaded.push(*coord);
+                evicted.push((*coord, voxels.clone()));
+            }
+            keep
+        });
+
+        // nobody waits for these anymore, insert_chunk drops them if they still come in
+        self.loading.retain(|coord| {
+            let keep = dist2(coord) <= far;
+            if !keep {
+                update.unloaded.push(*coord);
+            }
+            keep
+        });
+
+        // generated chunks can always be generated again, only edits need saving
+        if let Some(dir) = &self.save_dir {
+            let edited: Vec<_> = evicted
+                .iter()
+                .filter(|(coord, _)| self.modified.remove(coord))
+                .map(|(coord, voxels)| (*coord, voxels.as_slice()))
+                .collect();
+
+            if let Err(e) = region::save_chunks(dir, edited) {
+                println!("failed to save unloaded chunks: {e}");
+            }
+        }
+
+        let r = self.radius;
+
+        for x in -r..=r {
+            for y in -r..=r {
+                for z in -r..=r {
+                    let coord = [center[0] + x, center[1] + y, center[2] + z];
+                    if dist2(&coord) > r * r
+                        || self.chunks.contains_key(&coord)
+                        || !self.loading.insert(coord)
+                    {
+                        continue;
+                    }
+
+                    update.load.push(coord);
+                }
+            }
+        }
+
+        // closest first, so the area around the camera shows up before the horizon
+        update.load.sort_by_key(|c| dist2(c));
+
+        update
+    }
+
+    // job that produces the voxels of a chunk StreamUpdate::load asked for
+    pub fn load_job(&self, coord: ChunkCoord) -> ChunkLoadJob {
+        ChunkLoadJob {
+            coord,
+            terrain: TERRAIN.read().unwrap().clone(),
+            save_dir: self.save_dir.clone(),
+        }
+    }
+
+    // adds a chunk that finished loading and returns what needs (re)meshing: the chunk plus its
+    // loaded neighbours, which still have faces towards it. chunks unloaded in the meantime are
+    // dropped
+    pub fn insert_chunk(&mut self, coord: ChunkCoord, voxels: Vec<VoxelId>) -> Vec<ChunkCoord> {
+        if !self.loading.remove(&coord) {
+            return vec![];
+        }
+        self.chunks.insert(coord, Arc::new(voxels));
+
+        let mut remesh = vec![coord];
+        for ofs in NEIGHBOUR_OFFSETS {
+            let n = [coord[0] + ofs[0], coord[1] + ofs[1], coord[2] + ofs[2]];
+            if self.chunks.contains_key(&n) {
+                remesh.push(n);
+            }
+        }
+
+        remesh
+    }
+
+    // chunk a world-space voxel belongs to, and its position inside that chunk
+    pub fn split(pos: [i32; 3]) -> (ChunkCoord, [usize; 3]) {
+        let size = CHUNK_SIZE as i32;
+
+        (
+            pos.map(|c| c.div_euclid(size)),
+            pos.map(|c| c.rem_euclid(size) as usize),
+        )
+    }
+
+    // None if the chunk holding `pos` isn't loaded
+    pub fn get_voxel(&self, pos: [i32; 3]) -> Option<VoxelId> {
+        let (coord, [x, y, z]) = Self::split(pos);
+
+        self.chunks
+            .get(&coord)
+            .map(|voxels| voxels[vec3_to_idx(x, y, z, CHUNK_SIZE)])
+    }
+
+    // changes one voxel and marks its chunk dirty, plus the neighbours it touches when it sits
+    // on a chunk border. returns false if the chunk holding `pos` isn't loaded
+    pub fn set_voxel(&mut self, pos: [i32; 3], voxel: VoxelId) -> bool {
+        let (coord, local) = Self::split(pos);
+
+        let Some(voxels) = self.chunks.get_mut(&coord) else {
+            return false;
+        };
+
+        let idx = vec3_to_idx(local[0], local[1], local[2], CHUNK_SIZE);
+        if voxels[idx] == voxel {
+            return true;
+        }
+
+        // jobs already queued keep the old snapshot
+        Arc::make_mut(voxels)[idx] = voxel;
+        self.dirty.insert(coord);
+        self.modified.insert(coord);
+
+        for axis in 0..3 {
+            let mut ofs = [0; 3];
+            if local[axis] == 0 {
+                ofs[axis] = -1;
+            } else if local[axis] == CHUNK_SIZE - 1 {
+                ofs[axis] = 1;
+            } else {
+                continue;
+            }
+
+            let n = [coord[0] + ofs[0], coord[1] + ofs[1], coord[2] + ofs[2]];
+            if self.chunks.contains_key(&n) {
+                self.dirty.insert(n);
+            }
+        }
+
+        true
+    }
+
+    // writes the chunks edited since they were loaded to the save directory, if there is one
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(dir) = &self.save_dir else {
+            return Ok(());
+        };
+
+        region::save_chunks(
+            dir,
+            self.modified
+                .iter()
+                .filter_map(|coord| self.chunks.get(coord).map(|v| (*coord, v.as_slice()))),
+        )?;
+        self.modified.clear();
+
+        Ok(())
+    }
+
+    // dirty chunks, clearing the set
+    pub fn take_dirty(&mut self) -> Vec<ChunkCoord> {
+        self.dirty.drain().collect()
+    }
+
+    // snapshot of a loaded chunk and its neighbours for the mesher
+    pub fn mesh_job(&self, coord: ChunkCoord) -> Option<VoxelMeshGenJob> {
+        let voxels = self.chunk(coord)?;
+        let neighbours = NEIGHBOUR_OFFSETS.map(|ofs| {
+            self.chunk([coord[0] + ofs[0], coord[1] + ofs[1], coord[2] + ofs[2]])
+        });
+
+        Some(VoxelMeshGenJob::new(coord, voxels, neighbours))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    // world-space voxel that was hit
+    pub pos: [i32; 3],
+    // outward normal of the face the ray entered through, zero if the ray started inside the voxel
+    pub normal: [i32; 3],
+    pub distance: f32,
+}
+
+impl World {
+    // walks the voxel grid along the ray one cell at a time (amanatides & woo) and returns the
+    // first solid voxel within `max_dist`. unloaded chunks count as air
+    pub fn raycast(&self, origin: Vec3A, dir: Vec3A, max_dist: f32) -> Option<RayHit> {
+        let dir = dir.try_normalize()?;
+        let types = VOXEL_TYPES.read().unwrap();
+
+        let o = origin.to_array();
+        let d = dir.to_array();
+
+        let mut voxel = o.map(|c| c.floor() as i32);
+        let mut step = [0; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+
+        for axis in 0..3 {
+            if d[axis] > 0.0 {
+                step[axis] = 1;
+                t_max[axis] = (voxel[axis] as f32 + 1.0 - o[axis]) / d[axis];
+            } else if d[axis] < 0.0 {
+                step[axis] = -1;
+                t_max[axis] = (o[axis] - voxel[axis] as f32) / -d[axis];
+            } else {
+                continue;
+            }
+            t_delta[axis] = 1.0 / d[axis].abs();
+        }
+
+        let mut normal = [0; 3];
+        let mut distance = 0.0;
+
+        while distance <= max_dist {
+            if let Some(v) = self.get_voxel(voxel) {
+                if types.is_solid(v) {
+                    return Some(RayHit { pos: voxel, normal, distance });
+                }
+            }
+
+            let axis = if t_max[0] < t_max[1] {
+                if t_max[0] < t_max[2] { 0 } else { 2 }
+            } else if t_max[1] < t_max[2] { 1 } else { 2 };
+
+            distance = t_max[axis];
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+
+            normal = [0; 3];
+            normal[axis] = -step[axis];
+        }
+
+        None
+    }
+
+    // removes the voxel the ray hits first
+    pub fn dig(&mut self, origin: Vec3A, dir: Vec3A, max_dist: f32) -> Option<RayHit> {
+        let hit = self.raycast(origin, dir, max_dist)?;
+        self.set_voxel(hit.pos, AIR);
+
+        Some(hit)
+    }
+
+    // puts `voxel` against the face the ray hits first
+    pub fn place(&mut self, origin: Vec3A, dir: Vec3A, max_dist: f32, voxel: VoxelId) -> Option<RayHit> {
+        let hit = self.raycast(origin, dir, max_dist)?;
+        if hit.normal == [0; 3] {
+            return None;
+        }
+
+        let pos = [
+            hit.pos[0] + hit.normal[0],
+            hit.pos[1] + hit.normal[1],
+            hit.pos[2] + hit.normal[2],
+        ];
+
+        self.set_voxel(pos, voxel).then_some(hit)
+    }
+}
+
+// WORLD FILES //
+//
+// a world directory holds a `world.meta` file (magic "AWLD", format version u16, seed u64)
+// and the region files of its chunks
+
+const META_MAGIC: &[u8; 4] = b"AWLD";
+// of world.meta itself, the region files have their own
+const META_VERSION: u16 = 1;
+const META_FILE: &str = "world.meta";
+
+// writes every loaded chunk and the seed to `dir`, which becomes the world's save directory
+pub fn save_world(world: &mut World, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut meta = vec![];
+    meta.extend(META_MAGIC);
+    meta.extend(META_VERSION.to_le_bytes());
+    meta.extend(TERRAIN.read().unwrap().seed.to_le_bytes());
+    fs::write(dir.join(META_FILE), meta)?;
+
+    region::save_chunks(dir, world.chunks.iter().map(|(c, v)| (*c, v.as_slice())))?;
+
+    world.modified.clear();
+    world.save_dir = Some(dir.to_path_buf());
+
+    Ok(())
+}
+
+// opens the world in `dir` with the seed it was saved with. nothing is read up front, chunks
+// come off disk as they are streamed in and the ones never saved are generated
+pub fn load_world(dir: &Path, radius: i32) -> io::Result<World> {
+    let meta = fs::read(dir.join(META_FILE))?;
+    if meta.len() != 14 || &meta[0..4] != META_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a world directory"));
+    }
+
+    let version = u16::from_le_bytes([meta[4], meta[5]]);
+    if version != META_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported world format version {version}"),
+        ));
+    }
+
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&meta[6..14]);
+
+    let mut terrain = TERRAIN.read().unwrap().clone();
+    terrain.seed = u64::from_le_bytes(seed);
+    set_terrain(terrain);
+
+    let mut world = World::new(radius);
+    world.save_dir = Some(dir.to_path_buf());
+
+    Ok(world)
+}
+
+// the world saved in `dir`, or a new one saved there from now on if `dir` doesn't hold one yet
+pub fn open_world(dir: &Path, radius: i32) -> io::Result<World> {
+    if dir.join(META_FILE).exists() {
+        return load_world(dir, radius);
+    }
+
+    let mut world = World::new(radius);
+    save_world(&mut world, dir)?;
+
+    Ok(world)
+}
+
+pub static WORLD: Lazy<Mutex<World>> = Lazy::new(|| {
+    Mutex::new(World::new(DEFAULT_RADIUS))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Heightmap;
+    use crate::voxel::{GLASS, STONE};
+
+    // chunks -2..=1 on every axis loaded and empty, with stone at `solid`
+    fn world_with(solid: &[[i32; 3]]) -> World {
+        let mut world = World::new(0);
+        for x in -2..=1 {
+            for y in -2..=1 {
+                for z in -2..=1 {
+                    world.chunks.insert([x, y, z], Arc::new(vec![AIR; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE]));
+                }
+            }
+        }
+
+        for &pos in solid {
+            assert!(world.set_voxel(pos, STONE));
+        }
+
+        world
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn raycast_axis_aligned() {
+        let world = world_with(&[[5, 0, 0]]);
+
+        let hit = world.raycast(Vec3A::splat(0.5), Vec3A::X, 10.0).unwrap();
+        assert_eq!(hit.pos, [5, 0, 0]);
+        assert_eq!(hit.normal, [-1, 0, 0]);
+        assert_close(hit.distance, 4.5);
+
+        let hit = world.raycast(Vec3A::new(5.5, 4.5, 0.5), -Vec3A::Y, 10.0).unwrap();
+        assert_eq!(hit.pos, [5, 0, 0]);
+        assert_eq!(hit.normal, [0, 1, 0]);
+        assert_close(hit.distance, 3.5);
+    }
+
+    #[test]
+    fn raycast_diagonal() {
+        // the ray passes right next to [1, 2, 0] without touching it
+        let world = world_with(&[[1, 2, 0], [3, 3, 0]]);
+
+        let hit = world.raycast(Vec3A::new(0.5, 0.2, 0.5), Vec3A::new(1.0, 1.0, 0.0), 10.0).unwrap();
+        assert_eq!(hit.pos, [3, 3, 0]);
+        assert_eq!(hit.normal, [0, -1, 0]);
+        assert_close(hit.distance, 2.8 * std::f32::consts::SQRT_2);
+    }
+
+    #[test]
+    fn raycast_from_inside() {
+        let world = world_with(&[[0, 0, 0]]);
+
+        let hit = world.raycast(Vec3A::splat(0.5), Vec3A::Z, 10.0).unwrap();
+        assert_eq!(hit.pos, [0, 0, 0]);
+        assert_eq!(hit.normal, [0, 0, 0]);
+        assert_eq!(hit.distance, 0.0);
+    }
+
+    #[test]
+    fn raycast_max_dist() {
+        let world = world_with(&[[10, 0, 0]]);
+
+        assert_eq!(world.raycast(Vec3A::splat(0.5), Vec3A::X, 5.0), None);
+        assert!(world.raycast(Vec3A::splat(0.5), Vec3A::X, 10.0).is_some());
+    }
+
+    #[test]
+    fn raycast_negative_across_chunks() {
+        // three chunks along -x, in the z = -1 chunk row
+        let world = world_with(&[[-40, 2, -3]]);
+
+        let hit = world.raycast(Vec3A::new(1.5, 2.5, -2.5), -Vec3A::X, 64.0).unwrap();
+        assert_eq!(hit.pos, [-40, 2, -3]);
+        assert_eq!(hit.normal, [1, 0, 0]);
+        assert_close(hit.distance, 40.5);
+    }
+
+    // the dirty set after editing `pos` in a world with no pending remeshes
+    fn dirtied_by(pos: [i32; 3]) -> HashSet<ChunkCoord> {
+        let mut world = world_with(&[]);
+        assert!(world.set_voxel(pos, STONE));
+
+        world.take_dirty().into_iter().collect()
+    }
+
+    #[test]
+    fn set_voxel_marks_its_chunk() {
+        let mut world = world_with(&[]);
+
+        assert!(world.set_voxel([5, 6, 7], STONE));
+        assert_eq!(world.get_voxel([5, 6, 7]), Some(STONE));
+        assert_eq!(world.get_voxel([5, 6, 8]), Some(AIR));
+        assert_eq!(world.take_dirty(), vec![[0, 0, 0]]);
+        assert!(world.modified.contains(&[0, 0, 0]));
+
+        // taken, so nothing is left until the next edit
+        assert!(world.take_dirty().is_empty());
+
+        // negative positions belong to the chunks below zero
+        assert!(world.set_voxel([-2, -34, 5], STONE));
+        assert_eq!(world.get_voxel([-2, -34, 5]), Some(STONE));
+        assert_eq!(world.take_dirty(), vec![[-1, -2, 0]]);
+    }
+
+    #[test]
+    fn set_voxel_on_a_border_marks_the_neighbour() {
+        let last = CHUNK_SIZE as i32 - 1;
+
+        for axis in 0..3 {
+            let mut pos = [5, 6, 7];
+            let mut neighbour = [0, 0, 0];
+
+            pos[axis] = 0;
+            neighbour[axis] = -1;
+            assert_eq!(dirtied_by(pos), HashSet::from([[0, 0, 0], neighbour]), "low side of axis {axis}");
+
+            pos[axis] = last;
+            neighbour[axis] = 1;
+            assert_eq!(dirtied_by(pos), HashSet::from([[0, 0, 0], neighbour]), "high side of axis {axis}");
+        }
+
+        // a corner touches three neighbours
+        assert_eq!(
+            dirtied_by([0, last, 0]),
+            HashSet::from([[0, 0, 0], [-1, 0, 0], [0, 1, 0], [0, 0, -1]]),
+        );
+    }
+
+    #[test]
+    fn unloaded_chunks_are_left_alone() {
+        let mut world = world_with(&[]);
+        let far = [1000, 0, 0];
+
+        assert_eq!(world.get_voxel(far), None);
+        assert!(!world.set_voxel(far, STONE));
+        assert_eq!(world.get_voxel(far), None);
+        assert!(world.take_dirty().is_empty());
+
+        // the border neighbour isn't loaded, only the owning chunk gets marked
+        assert_eq!(dirtied_by([CHUNK_SIZE as i32 * 2 - 1, 5, 5]), HashSet::from([[1, 0, 0]]));
+    }
+
+    #[test]
+    fn setting_the_same_voxel_changes_nothing() {
+        let mut world = world_with(&[]);
+
+        assert!(world.set_voxel([0, 0, 0], AIR));
+        assert!(world.take_dirty().is_empty());
+        assert!(world.modified.is_empty());
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arendp-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    // streams in the chunk around the origin the way mesh_gen::stream does, minus the workers
+    fn stream_origin(world: &mut World) {
+        let update = world.update(Vec3A::splat(0.5));
+        assert_eq!(update.load, vec![[0, 0, 0]]);
+
+        let voxels = world.load_job([0, 0, 0]).run();
+        assert_eq!(world.insert_chunk([0, 0, 0], voxels), vec![[0, 0, 0]]);
+    }
+
+    #[test]
+    fn world_round_trip() {
+        let dir = temp_dir("world");
+        set_terrain(Terrain::new(1234, Heightmap::default()));
+
+        let mut world = open_world(&dir, 0).unwrap();
+        assert!(dir.join(META_FILE).exists());
+        stream_origin(&mut world);
+
+        assert!(world.set_voxel([3, 4, 5], GLASS));
+        world.save().unwrap();
+
+        // whatever runs next, the saved seed wins
+        set_terrain(Terrain::new(99, Heightmap::default()));
+        let mut loaded = open_world(&dir, 0).unwrap();
+        assert_eq!(TERRAIN.read().unwrap().seed, 1234);
+        assert_eq!(loaded.save_dir.as_deref(), Some(dir.as_path()));
+
+        // nothing is read until the chunk streams back in
+        assert_eq!(loaded.get_voxel([3, 4, 5]), None);
+        stream_origin(&mut loaded);
+        assert_eq!(loaded.get_voxel([3, 4, 5]), Some(GLASS));
+        assert_eq!(loaded.chunk([0, 0, 0]), world.chunk([0, 0, 0]));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn load_world_checks_the_meta_file() {
+        let dir = temp_dir("world-meta");
+        fs::create_dir_all(&dir).unwrap();
+        let meta = |magic: &[u8; 4], version: u16| {
+            let mut meta = magic.to_vec();
+            meta.extend(version.to_le_bytes());
+            meta.extend(7u64.to_le_bytes());
+            meta
+        };
+
+        assert!(load_world(&dir, 0).is_err());
+
+        fs::write(dir.join(META_FILE), meta(b"NOPE", META_VERSION)).unwrap();
+        assert_eq!(load_world(&dir, 0).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        fs::write(dir.join(META_FILE), meta(META_MAGIC, META_VERSION + 1)).unwrap();
+        assert_eq!(load_world(&dir, 0).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        // cut off in the middle of the seed
+        fs::write(dir.join(META_FILE), &meta(META_MAGIC, META_VERSION)[..10]).unwrap();
+        assert_eq!(load_world(&dir, 0).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}